@@ -0,0 +1,224 @@
+use std::{
+    cmp::Reverse,
+    sync::atomic::AtomicBool,
+    time::{Duration, Instant},
+};
+
+use crate::game::{BoardSize, Column, ConnectFourBoard, MAX_SLOTS};
+
+use super::{INFINITY, Score, Search, Solver, TranspositionTable, move_order, with_helpers};
+
+/// How much effort `analyze` spends on each column.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SearchLimits {
+    Depth(u8),
+    Time(Duration),
+    /// Solves every column exactly, which can take minutes early in the game. Only positions
+    /// of the standard rules can be solved, others get no scores at all.
+    Perfect,
+}
+
+/// Scores every playable column for the player to move, ordered from the first to the
+/// last column. Searches run on `threads` threads, while solving always runs on one.
+pub fn analyze(
+    board: &ConnectFourBoard,
+    limits: SearchLimits,
+    threads: usize,
+) -> Vec<(Column, Score)> {
+    let mut board = board.clone();
+    let table = TranspositionTable::default();
+    let mut scores = match limits {
+        SearchLimits::Depth(depth) => {
+            let depth = depth.max(1);
+            let mut search = Search::new(&table, None, None);
+            let position = board.clone();
+            with_helpers(
+                &position,
+                depth,
+                None,
+                &table,
+                search.evaluator,
+                threads,
+                || search_columns(&mut search, &mut board, depth),
+            )
+            .0
+        }
+        SearchLimits::Time(thinking_time) => {
+            let deadline = Instant::now() + thinking_time;
+            let max_depth = board.moves_left().unwrap_or(MAX_SLOTS);
+            let mut search = Search::new(&table, None, None);
+            let position = board.clone();
+            with_helpers(
+                &position,
+                max_depth,
+                Some(deadline),
+                &table,
+                search.evaluator,
+                threads,
+                || {
+                    // The first depth always runs to the end, so every column is scored
+                    // however little time there is.
+                    let mut scores = search_columns(&mut search, &mut board, 1);
+                    search.deadline = Some(deadline);
+                    for depth in 2..=max_depth {
+                        let completed = search_columns(&mut search, &mut board, depth);
+                        if search.stopped {
+                            break;
+                        }
+                        scores = completed;
+                    }
+                    scores
+                },
+            )
+            .0
+        }
+        SearchLimits::Perfect => {
+            solve_columns(&mut Solver::for_board(&board), &mut board).unwrap_or_default()
+        }
+    };
+
+    scores.sort_by_key(|(column, _)| column.to_index());
+    scores
+}
+
+/// Strongest column for the player to move along with its score. Ties go to the column
+/// closest to the center.
+pub fn best_move(
+    board: &ConnectFourBoard,
+    limits: SearchLimits,
+    threads: usize,
+) -> Option<(Column, Score)> {
+    strongest(&analyze(board, limits, threads), board.size())
+}
+
+/// Highest scoring column, with ties going to the column closest to the center.
+pub(super) fn strongest(scores: &[(Column, Score)], size: BoardSize) -> Option<(Column, Score)> {
+    move_order(size.width())
+        .filter_map(|column| scores.iter().find(|(c, _)| *c == column))
+        .min_by_key(|(_, score)| Reverse(score.rank()))
+        .copied()
+}
+
+fn search_columns(
+    search: &mut Search,
+    board: &mut ConnectFourBoard,
+    depth: u8,
+) -> Vec<(Column, Score)> {
+    let mut scores = Vec::with_capacity(board.size().width() as usize);
+    for column in move_order(board.size().width()) {
+        if board.try_move(column).is_err() {
+            continue;
+        }
+        // Every column is searched with a full window so its score is exact rather than a bound.
+        let score = -search.negamax(board, depth - 1, -INFINITY, INFINITY);
+        board.pop_move();
+
+        let score = Score::from_search(score, board.move_count());
+        scores.push((column, score));
+    }
+    scores
+}
+
+/// Solves every playable column, or returns `None` for positions of other rules than the
+/// standard ones.
+pub(super) fn solve_columns(
+    solver: &mut Solver,
+    board: &mut ConnectFourBoard,
+) -> Option<Vec<(Column, Score)>> {
+    // Without limits, the only positions given up on are those of other rules.
+    try_solve_columns(solver, board, None, None)
+}
+
+/// Solves every column like `solve_columns`, but also gives up with `None` once `deadline`
+/// passes or `stop` is set.
+pub(super) fn try_solve_columns(
+    solver: &mut Solver,
+    board: &mut ConnectFourBoard,
+    deadline: Option<Instant>,
+    stop: Option<&AtomicBool>,
+) -> Option<Vec<(Column, Score)>> {
+    let mut scores = Vec::with_capacity(board.size().width() as usize);
+    for column in move_order(board.size().width()) {
+        if board.try_move(column).is_err() {
+            continue;
+        }
+        let score = solver.try_solve(board, deadline, stop);
+        board.pop_move();
+
+        scores.push((column, score?.previous()));
+    }
+    Some(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        ai::Score,
+        game::{Column, ConnectFourBoard, test_positions::double_threat},
+    };
+
+    use super::{SearchLimits, analyze, best_move};
+
+    fn expected_scores() -> Vec<(Column, Score)> {
+        vec![
+            (Column::One, Score::Win(1)),
+            (Column::Two, Score::Loss(1)),
+            (Column::Three, Score::Loss(1)),
+            (Column::Four, Score::Loss(1)),
+            (Column::Five, Score::Loss(1)),
+            (Column::Six, Score::Loss(1)),
+            (Column::Seven, Score::Loss(1)),
+        ]
+    }
+
+    #[test]
+    fn analyzes_every_column_to_depth() {
+        for threads in [1, 3] {
+            let scores = analyze(&double_threat(), SearchLimits::Depth(4), threads);
+            assert_eq!(expected_scores(), scores);
+        }
+    }
+
+    #[test]
+    fn analyzes_every_column_in_time() {
+        let limits = SearchLimits::Time(Duration::from_millis(100));
+        for threads in [1, 3] {
+            assert_eq!(
+                expected_scores(),
+                analyze(&double_threat(), limits, threads)
+            );
+        }
+    }
+
+    #[test]
+    fn scores_every_column_without_time_to_think() {
+        let scores = analyze(&double_threat(), SearchLimits::Time(Duration::ZERO), 1);
+        assert_eq!(7, scores.len());
+        assert_eq!((Column::One, Score::Win(1)), scores[0]);
+    }
+
+    #[test]
+    fn analyzes_every_column_perfectly() {
+        let scores = analyze(&double_threat(), SearchLimits::Perfect, 1);
+        assert_eq!(expected_scores(), scores);
+    }
+
+    #[test]
+    fn picks_best_move() {
+        let best = best_move(&double_threat(), SearchLimits::Depth(4), 1);
+        assert_eq!(Some((Column::One, Score::Win(1))), best);
+
+        let opening = best_move(&ConnectFourBoard::default(), SearchLimits::Depth(2), 1);
+        assert_eq!(Some(Column::Four), opening.map(|(column, _)| column));
+    }
+
+    #[test]
+    fn skips_full_columns() {
+        let board = ConnectFourBoard::from_moves("111111").unwrap();
+        let scores = analyze(&board, SearchLimits::Depth(2), 1);
+        assert_eq!(6, scores.len());
+        assert!(scores.iter().all(|(column, _)| *column != Column::One));
+    }
+}
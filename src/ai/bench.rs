@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use crate::game::ConnectFourBoard;
+
+use super::{Evaluator, TranspositionTable, parallel_root};
+
+// Openings and middlegames of varying complexity, given as move sequences.
+const BENCH_POSITIONS: [&str; 8] = [
+    "",
+    "4453",
+    "44444",
+    "3344",
+    "4455443",
+    "354453",
+    "3424536",
+    "12345671234567",
+];
+
+/// Result of searching every benchmark position.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BenchReport {
+    pub depth: u8,
+    pub threads: usize,
+    pub positions: usize,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchReport {
+    pub fn nodes_per_second(&self) -> u64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            return 0;
+        }
+        (self.nodes as f64 / seconds) as u64
+    }
+}
+
+/// Searches a fixed set of positions to `depth` with `threads` threads, each with an empty
+/// transposition table, so runs are comparable between builds, evaluators and thread
+/// counts. Nodes are counted over every thread.
+pub fn bench(depth: u8, evaluator: &dyn Evaluator, threads: usize) -> BenchReport {
+    let depth = depth.max(1);
+    let threads = threads.max(1);
+    let mut nodes = 0;
+    let start = Instant::now();
+    for moves in BENCH_POSITIONS {
+        let board = ConnectFourBoard::from_moves(moves).expect("valid bench position");
+        let table = TranspositionTable::default();
+        let depth = depth.min(board.moves_left().unwrap_or(depth));
+        nodes += parallel_root(&board, depth, &table, evaluator, threads).1;
+    }
+
+    BenchReport {
+        depth,
+        threads,
+        positions: BENCH_POSITIONS.len(),
+        nodes,
+        elapsed: start.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::{EvalWeights, ThreatEvaluator};
+
+    use super::{BENCH_POSITIONS, bench};
+
+    #[test]
+    fn searches_every_position() {
+        for report in [
+            bench(2, &EvalWeights::DEFAULT, 1),
+            bench(2, &ThreatEvaluator, 1),
+            bench(2, &EvalWeights::DEFAULT, 3),
+        ] {
+            assert_eq!(BENCH_POSITIONS.len(), report.positions);
+            assert!(report.nodes >= BENCH_POSITIONS.len() as u64);
+        }
+    }
+}
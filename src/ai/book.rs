@@ -0,0 +1,386 @@
+//! Opening books of solved positions, generated offline so the AI can play the opening
+//! perfectly without searching.
+//!
+//! Books are stored as a small header followed by one entry per position, sorted by the
+//! position's canonical key so mirrored positions share an entry:
+//!
+//! - the magic bytes `C4BK` and the format version,
+//! - the board width and height, the line length and the number of plies covered,
+//! - the number of entries as a little-endian `u32`,
+//! - every entry as a little-endian `u64` key and a signed byte score, where a positive
+//!   score wins with that many moves, a negative one loses with that many and zero draws.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::OnceLock,
+};
+
+use thiserror::Error;
+
+use crate::game::{BoardSize, BoardStatus, Column, ConnectFourBoard, Rules, Variant};
+
+use super::{Score, Solver, analysis::strongest, move_order};
+
+const MAGIC: &[u8; 4] = b"C4BK";
+const VERSION: u8 = 2;
+
+// Entries reserved up front while reading, as the count in a damaged file can be anything.
+const MAX_RESERVED: usize = 1 << 16;
+
+static INSTALLED: OnceLock<Book> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum BookError {
+    #[error("Could not access the book. {0}")]
+    Io(#[from] io::Error),
+
+    #[error("File is not an opening book.")]
+    InvalidFormat,
+
+    #[error("Books can only be built for the standard rules.")]
+    UnsupportedVariant,
+}
+
+/// Solved scores of every position up to a number of plies into the game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Book {
+    size: BoardSize,
+    rules: Rules,
+    plies: u8,
+    /// Canonical keys with their scores, sorted by key.
+    entries: Vec<(u64, i8)>,
+}
+
+impl Book {
+    /// Solves every position that can be reached within `plies` moves on an empty board.
+    /// Deeper positions are solved first, which fills the solver's table with results that
+    /// cut the searches of the earlier positions short. `progress` is called with the number
+    /// of positions solved so far and the total after every position.
+    pub fn generate(
+        size: BoardSize,
+        rules: Rules,
+        plies: u8,
+        solver: &mut Solver,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<Self, BookError> {
+        if rules.variant() != Variant::Standard {
+            return Err(BookError::UnsupportedVariant);
+        }
+
+        let mut layers = vec![vec![ConnectFourBoard::with_rules(size, rules)]];
+        for _ in 0..plies {
+            let mut next = HashMap::new();
+            for board in layers.last().expect("layers start with the empty board") {
+                for column in size.columns() {
+                    let mut child = board.clone();
+                    if child.try_move(column).is_ok() && child.status() == BoardStatus::OnGoing {
+                        next.entry(child.canonical_key()).or_insert(child);
+                    }
+                }
+            }
+            layers.push(next.into_values().collect());
+        }
+
+        let total = layers.iter().map(Vec::len).sum();
+        let mut entries = Vec::with_capacity(total);
+        for board in layers.iter().rev().flatten() {
+            let score = solver
+                .solve(board)
+                .expect("books are only built for the standard rules");
+            entries.push((board.canonical_key(), encode(score)));
+            progress(entries.len(), total);
+        }
+        entries.sort_unstable_by_key(|&(key, _)| key);
+
+        Ok(Self {
+            size,
+            rules,
+            plies,
+            entries,
+        })
+    }
+
+    pub fn size(&self) -> BoardSize {
+        self.size
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Moves into the game the book covers.
+    pub fn plies(&self) -> u8 {
+        self.plies
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Solved score of the position, if the book covers it.
+    pub fn score(&self, board: &ConnectFourBoard) -> Option<Score> {
+        if board.size() != self.size
+            || board.rules() != self.rules
+            || board.move_count() > self.plies as u16
+        {
+            return None;
+        }
+        let idx = self
+            .entries
+            .binary_search_by_key(&board.canonical_key(), |&(key, _)| key)
+            .ok()?;
+        Some(decode(self.entries[idx].1))
+    }
+
+    /// Solved score of every playable column, ordered from the center outwards, if the book
+    /// covers the positions they lead to.
+    pub fn columns(&self, board: &ConnectFourBoard) -> Option<Vec<(Column, Score)>> {
+        if board.status() != BoardStatus::OnGoing {
+            return None;
+        }
+
+        let mut board = board.clone();
+        let mut scores = Vec::with_capacity(board.size().width() as usize);
+        for column in move_order(board.size().width()) {
+            if board.try_move(column).is_err() {
+                continue;
+            }
+            let score = match board.status() {
+                BoardStatus::Winner(_) => Some(Score::Loss(0)),
+                BoardStatus::Draw => Some(Score::Draw),
+                BoardStatus::OnGoing => self.score(&board),
+            };
+            board.pop_move();
+
+            scores.push((column, score?.previous()));
+        }
+        Some(scores)
+    }
+
+    /// Strongest column for the player to move along with its score, if the book covers
+    /// every position it could lead to. Ties go to the column closest to the center.
+    pub fn best_move(&self, board: &ConnectFourBoard) -> Option<(Column, Score)> {
+        strongest(&self.columns(board)?, board.size())
+    }
+
+    /// Makes this the book the `next_move` functions and the hard and perfect levels
+    /// consult before searching. Only one book can be installed, so a second one is
+    /// handed back.
+    pub fn install(self) -> Result<(), Self> {
+        INSTALLED.set(self)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BookError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BookError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[
+            VERSION,
+            self.size.width(),
+            self.size.height(),
+            self.rules.connect(),
+            self.plies,
+        ])?;
+        writer.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for &(key, score) in &self.entries {
+            writer.write_all(&key.to_le_bytes())?;
+            writer.write_all(&score.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> Result<Self, BookError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let mut fields = [0; 5];
+        reader.read_exact(&mut fields)?;
+        let [version, width, height, connect, plies] = fields;
+        let mut count = [0; 4];
+        reader.read_exact(&mut count)?;
+        if magic != *MAGIC || version != VERSION {
+            return Err(BookError::InvalidFormat);
+        }
+        let size = BoardSize::new(width, height).map_err(|_| BookError::InvalidFormat)?;
+        let rules = Rules::new(Variant::Standard, connect).map_err(|_| BookError::InvalidFormat)?;
+
+        let count = u32::from_le_bytes(count) as usize;
+        let mut entries = Vec::with_capacity(count.min(MAX_RESERVED));
+        let mut entry = [0; 9];
+        for _ in 0..count {
+            reader.read_exact(&mut entry)?;
+            let [key @ .., score] = entry;
+            entries.push((u64::from_le_bytes(key), score as i8));
+        }
+        if !entries.is_sorted_by_key(|&(key, _)| key) {
+            return Err(BookError::InvalidFormat);
+        }
+
+        Ok(Self {
+            size,
+            rules,
+            plies,
+            entries,
+        })
+    }
+}
+
+pub(super) fn installed() -> Option<&'static Book> {
+    INSTALLED.get()
+}
+
+fn encode(score: Score) -> i8 {
+    match score {
+        Score::Win(moves) => moves as i8,
+        Score::Loss(moves) => -(moves as i8),
+        Score::Draw | Score::Eval(_) => 0,
+    }
+}
+
+fn decode(score: i8) -> Score {
+    match score {
+        0 => Score::Draw,
+        moves if moves > 0 => Score::Win(moves as u8),
+        moves => Score::Loss(moves.unsigned_abs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        ai::{AiLevel, Score, Solver, TranspositionTable, level, next_move, next_move_timed},
+        game::{BoardSize, Column, ConnectFourBoard, Move, Rules, Variant},
+    };
+
+    use super::{Book, BookError};
+
+    fn small_book() -> Book {
+        let mut solver = Solver::new(TranspositionTable::new(1 << 16));
+        let size = BoardSize::new(5, 4).unwrap();
+        Book::generate(size, Rules::STANDARD, 3, &mut solver, |_, _| {}).unwrap()
+    }
+
+    fn board_from(moves: &str) -> ConnectFourBoard {
+        ConnectFourBoard::from_moves_with_size(moves, BoardSize::new(5, 4).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn covers_positions_up_to_its_plies() {
+        let book = small_book();
+        // The empty board, then 3, 13 and 49 positions after each ply once mirror images
+        // and transpositions are merged.
+        assert_eq!(1 + 3 + 13 + 49, book.len());
+
+        let mut solver = Solver::new(TranspositionTable::new(1 << 16));
+        for moves in ["", "3", "23", "514"] {
+            let board = board_from(moves);
+            assert_eq!(Some(solver.solve(&board).unwrap()), book.score(&board));
+        }
+        assert_eq!(book.score(&board_from("12")), book.score(&board_from("54")));
+        assert_eq!(None, book.score(&board_from("1234")));
+        assert_eq!(
+            None,
+            book.score(&ConnectFourBoard::from_moves("3").unwrap())
+        );
+    }
+
+    #[test]
+    fn picks_solved_moves() {
+        let book = small_book();
+        let board = board_from("33");
+        let mut solver = Solver::new(TranspositionTable::new(1 << 16));
+        let (column, score) = book.best_move(&board).unwrap();
+        assert_eq!(solver.solve(&board).unwrap(), score);
+
+        let mut played = board.clone();
+        played.try_move(column).unwrap();
+        assert_eq!(score, solver.solve(&played).unwrap().previous());
+        // Answers to the last covered ply would lead out of the book.
+        assert_eq!(None, book.best_move(&board_from("333")));
+        assert!(book.columns(&board_from("333")).is_none());
+        assert_eq!(Column::Three, book.best_move(&board_from("")).unwrap().0);
+    }
+
+    #[test]
+    fn consulted_by_next_move() {
+        // A shallow search prefers the second column, while the book knows only the center
+        // holds the draw.
+        let board = board_from("");
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(
+            Some(Move::Drop(Column::Two)),
+            next_move(&board, 2, &mut table)
+        );
+
+        // No other test plays on this board size, so installing the book leaves them be.
+        small_book().install().unwrap();
+        assert_eq!(
+            Some(Move::Drop(Column::Three)),
+            next_move(&board, 2, &mut table)
+        );
+        assert_eq!(
+            Some(Move::Drop(Column::Three)),
+            next_move_timed(&board, Duration::ZERO, &mut table)
+        );
+
+        // Only the strongest levels play from the book.
+        assert_eq!(
+            Some(Move::Drop(Column::Three)),
+            level::opening_move(&board, AiLevel::Hard)
+        );
+        assert_eq!(None, level::opening_move(&board, AiLevel::Medium));
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let book = small_book();
+        let mut bytes = Vec::new();
+        book.write_to(&mut bytes).unwrap();
+        assert_eq!(13 + 9 * book.len(), bytes.len());
+        assert_eq!(book, Book::read_from(&bytes[..]).unwrap());
+
+        bytes[0] = b'X';
+        assert!(matches!(
+            Book::read_from(&bytes[..]),
+            Err(BookError::InvalidFormat)
+        ));
+        assert!(matches!(
+            Book::read_from(&bytes[..8]),
+            Err(BookError::Io(_))
+        ));
+
+        // A count far beyond the entries that follow runs out of bytes instead of memory.
+        bytes[0] = b'C';
+        bytes[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(Book::read_from(&bytes[..]), Err(BookError::Io(_))));
+    }
+
+    #[test]
+    fn rejects_popout() {
+        let rules = Rules::new(Variant::PopOut, 4).unwrap();
+        let mut solver = Solver::new(TranspositionTable::new(16));
+        assert!(matches!(
+            Book::generate(BoardSize::STANDARD, rules, 2, &mut solver, |_, _| {}),
+            Err(BookError::UnsupportedVariant)
+        ));
+        assert_eq!(Score::Draw, super::decode(super::encode(Score::Draw)));
+        assert_eq!(Score::Loss(3), super::decode(super::encode(Score::Loss(3))));
+    }
+}
@@ -0,0 +1,386 @@
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::game::{
+    BoardSize, ConnectFourBoard, MAX_CONNECT, MAX_HEIGHT, MAX_WIDTH, MIN_CONNECT, MIN_SIZE, Player,
+    Rules, winning_position,
+};
+
+// Number of bits in the bitboard of the largest board.
+const MAX_BITS: usize = MAX_WIDTH as usize * (MAX_HEIGHT as usize + 1);
+
+type SizeMaps =
+    [[[u8; MAX_BITS]; (MAX_HEIGHT - MIN_SIZE + 1) as usize]; (MAX_WIDTH - MIN_SIZE + 1) as usize];
+
+// Each position represents the number of connect 4's that overlap in that position. The
+// evaluation was tuned with this table on the standard board, so it is kept there even though
+// the second and fifth slots of the center column really take part in 10 lines each.
+#[rustfmt::skip]
+const CONNECT_FOUR_MAP: [u8; 49] = [
+    3, 4, 5, 5, 4, 3, 0,
+    4, 6, 8, 8, 6, 4, 0,
+    5, 8, 11, 11, 8, 5, 0,
+    7, 9, 13, 13, 9, 7, 0,
+    5, 8, 11, 11, 8, 5, 0,
+    4, 6, 8, 8, 6, 4, 0,
+    3, 4, 5, 5, 4, 3, 0,
+];
+
+// For every line length and board size, the number of lines that overlap in each position.
+static LINE_MAPS: [SizeMaps; (MAX_CONNECT - MIN_CONNECT + 1) as usize] = line_maps();
+
+const THREAT_SCORE: i32 = 8;
+const PARITY_SCORE: i32 = 16;
+const STACKED_SCORE: i32 = 32;
+const IMMEDIATE_SCORE: i32 = 500;
+
+/// Heuristic score of an undecided position, positive when it favors player one.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, board: &ConnectFourBoard) -> i32;
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Unknown evaluation '{0}'.")]
+pub struct UnknownEvaluation(String);
+
+/// Evaluators that can be picked by name.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Evaluation {
+    #[default]
+    Table,
+    Threats,
+}
+
+impl fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Evaluation::Table => write!(f, "table"),
+            Evaluation::Threats => write!(f, "threats"),
+        }
+    }
+}
+
+impl FromStr for Evaluation {
+    type Err = UnknownEvaluation;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Evaluation::Table),
+            "threats" => Ok(Evaluation::Threats),
+            _ => Err(UnknownEvaluation(s.into())),
+        }
+    }
+}
+
+impl Evaluation {
+    pub fn evaluator(self) -> Box<dyn Evaluator> {
+        match self {
+            Evaluation::Table => Box::new(EvalWeights::default()),
+            Evaluation::Threats => Box::new(ThreatEvaluator),
+        }
+    }
+}
+
+/// Weights of the terms in the table evaluation of a position. The default only counts
+/// discs on slots that take part in many winning lines.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EvalWeights {
+    /// Discs on slots that take part in many winning lines.
+    pub position: i32,
+    /// Empty slots that would complete a winning line.
+    pub threats: i32,
+}
+
+impl EvalWeights {
+    pub const DEFAULT: Self = Self {
+        position: 1,
+        threats: 0,
+    };
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Evaluator for EvalWeights {
+    fn evaluate(&self, board: &ConnectFourBoard) -> i32 {
+        let (size, connect) = (board.size(), board.connect());
+        let position = position_score(
+            board.player_one_bitboard(),
+            board.player_two_bitboard(),
+            size,
+            connect,
+        );
+        if self.threats == 0 {
+            return self.position * position;
+        }
+
+        let mask = board.player_one_bitboard() | board.player_two_bitboard();
+        let threats = winning_position(board.player_one_bitboard(), mask, size, connect)
+            .count_ones() as i32
+            - winning_position(board.player_two_bitboard(), mask, size, connect).count_ones()
+                as i32;
+        self.position * position + self.threats * threats
+    }
+}
+
+/// Scores the empty slots that would complete a winning line for either player.
+///
+/// Once the other columns fill up, player one gets to claim the slots on odd rows and
+/// player two those on even rows, so threats with the right parity are worth more. Threats
+/// above an opponent threat in the same column never come into play, while two threats on
+/// top of each other win as soon as the lower one becomes playable.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ThreatEvaluator;
+
+impl Evaluator for ThreatEvaluator {
+    fn evaluate(&self, board: &ConnectFourBoard) -> i32 {
+        evaluate_threats(
+            board.player_one_bitboard(),
+            board.player_two_bitboard(),
+            board.current_player(),
+            board.size(),
+            board.connect(),
+        )
+    }
+}
+
+fn evaluate_threats(
+    player_one: u128,
+    player_two: u128,
+    to_move: Player,
+    size: BoardSize,
+    connect: u8,
+) -> i32 {
+    let mask = player_one | player_two;
+    let playable = (mask + size.bottom_mask()) & size.board_mask();
+    let one_threats = winning_position(player_one, mask, size, connect);
+    let two_threats = winning_position(player_two, mask, size, connect);
+
+    // The player to move wins on the spot, while the other player wins next move unless
+    // their playable threats can all be blocked at once.
+    let (own, other) = match to_move {
+        Player::One => (one_threats, two_threats),
+        Player::Two => (two_threats, one_threats),
+    };
+    let immediate = if own & playable != 0 {
+        IMMEDIATE_SCORE
+    } else if (other & playable).count_ones() > 1 {
+        -IMMEDIATE_SCORE
+    } else {
+        0
+    };
+    let immediate = match to_move {
+        Player::One => immediate,
+        Player::Two => -immediate,
+    };
+
+    let (odd_rows, even_rows) = row_parities(size);
+    let one_useful = one_threats & !above(two_threats, size);
+    let two_useful = two_threats & !above(one_threats, size);
+    immediate
+        + position_score(player_one, player_two, size, connect)
+        + threat_score(one_useful, odd_rows)
+        - threat_score(two_useful, even_rows)
+}
+
+fn threat_score(threats: u128, parity: u128) -> i32 {
+    THREAT_SCORE * threats.count_ones() as i32
+        + PARITY_SCORE * (threats & parity).count_ones() as i32
+        + STACKED_SCORE * (threats & (threats >> 1)).count_ones() as i32
+}
+
+/// Slots on rows one, three, five and so on counted from the bottom, followed by the slots
+/// on the rows in between.
+fn row_parities(size: BoardSize) -> (u128, u128) {
+    let (mut odd, mut even) = (0u128, 0u128);
+    for row in 0..size.height() {
+        match row % 2 {
+            0 => odd |= 1 << row,
+            _ => even |= 1 << row,
+        }
+    }
+    // Every column repeats the pattern, which fits below the next column's bottom slot.
+    (odd * size.bottom_mask(), even * size.bottom_mask())
+}
+
+/// Slots above any of the given slots in the same column.
+fn above(slots: u128, size: BoardSize) -> u128 {
+    let mut above = (slots << 1) & size.board_mask();
+    for _ in 2..size.height() {
+        above |= (above << 1) & size.board_mask();
+    }
+    above
+}
+
+fn position_score(player_one: u128, player_two: u128, size: BoardSize, connect: u8) -> i32 {
+    let map = &LINE_MAPS[(connect - MIN_CONNECT) as usize][(size.width() - MIN_SIZE) as usize]
+        [(size.height() - MIN_SIZE) as usize];
+    let sum = |mut discs: u128| {
+        let mut sum = 0;
+        while discs != 0 {
+            sum += map[discs.trailing_zeros() as usize] as i32;
+            discs &= discs - 1;
+        }
+        sum
+    };
+    sum(player_one) - sum(player_two)
+}
+
+const fn line_maps() -> [SizeMaps; (MAX_CONNECT - MIN_CONNECT + 1) as usize] {
+    let mut maps = [[[[0; MAX_BITS]; (MAX_HEIGHT - MIN_SIZE + 1) as usize];
+        (MAX_WIDTH - MIN_SIZE + 1) as usize];
+        (MAX_CONNECT - MIN_CONNECT + 1) as usize];
+    let mut connect = MIN_CONNECT;
+    while connect <= MAX_CONNECT {
+        let mut width = MIN_SIZE;
+        while width <= MAX_WIDTH {
+            let mut height = MIN_SIZE;
+            while height <= MAX_HEIGHT {
+                maps[(connect - MIN_CONNECT) as usize][(width - MIN_SIZE) as usize]
+                    [(height - MIN_SIZE) as usize] =
+                    line_map(width as i32, height as i32, connect as i32);
+                height += 1;
+            }
+            width += 1;
+        }
+        connect += 1;
+    }
+
+    let size = BoardSize::STANDARD;
+    let standard = &mut maps[(Rules::STANDARD.connect() - MIN_CONNECT) as usize]
+        [(size.width() - MIN_SIZE) as usize][(size.height() - MIN_SIZE) as usize];
+    let mut bit = 0;
+    while bit < CONNECT_FOUR_MAP.len() {
+        standard[bit] = CONNECT_FOUR_MAP[bit];
+        bit += 1;
+    }
+    maps
+}
+
+/// Counts the lines of `connect` discs every slot of a `width` by `height` board takes part in.
+const fn line_map(width: i32, height: i32, connect: i32) -> [u8; MAX_BITS] {
+    const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+    let mut map = [0; MAX_BITS];
+    let mut col = 0;
+    while col < width {
+        let mut row = 0;
+        while row < height {
+            let mut count = 0;
+            let mut direction = 0;
+            while direction < DIRECTIONS.len() {
+                let (dc, dr) = DIRECTIONS[direction];
+                let mut offset = 0;
+                while offset < connect {
+                    let (start_col, start_row) = (col - offset * dc, row - offset * dr);
+                    let (end_col, end_row) = (
+                        start_col + (connect - 1) * dc,
+                        start_row + (connect - 1) * dr,
+                    );
+                    if start_col >= 0
+                        && end_col < width
+                        && start_row >= 0
+                        && start_row < height
+                        && end_row >= 0
+                        && end_row < height
+                    {
+                        count += 1;
+                    }
+                    offset += 1;
+                }
+                direction += 1;
+            }
+            map[(col * (height + 1) + row) as usize] = count;
+            row += 1;
+        }
+        col += 1;
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{BoardSize, Player};
+
+    use super::{
+        CONNECT_FOUR_MAP, Evaluation, LINE_MAPS, UnknownEvaluation, above, evaluate_threats,
+        line_map, row_parities,
+    };
+
+    const fn slot(col: u32, row: u32) -> u128 {
+        1 << (col * 7 + row)
+    }
+
+    #[test]
+    fn parses_evaluations() {
+        assert_eq!(Ok(Evaluation::Threats), "threats".parse());
+        assert_eq!(Ok(Evaluation::Table), "table".parse());
+        assert_eq!(
+            Err(UnknownEvaluation("tables".into())),
+            "tables".parse::<Evaluation>()
+        );
+    }
+
+    #[test]
+    fn rewards_threats_with_own_parity() {
+        // Three in a row on the third row from the bottom, with open slots on either side.
+        let three = slot(2, 2) | slot(3, 2) | slot(4, 2) | slot(2, 1) | slot(3, 0) | slot(4, 1);
+        let support = slot(2, 0) | slot(3, 1) | slot(4, 0);
+
+        let as_player_one = evaluate_threats(three, support, Player::One, BoardSize::STANDARD, 4);
+        let as_player_two = -evaluate_threats(support, three, Player::One, BoardSize::STANDARD, 4);
+        assert!(as_player_one > 0);
+        assert!(as_player_two > 0);
+        assert!(as_player_one > as_player_two);
+    }
+
+    #[test]
+    fn finds_slots_above() {
+        assert_eq!(
+            slot(5, 3) | slot(5, 4) | slot(5, 5),
+            above(slot(5, 2), BoardSize::STANDARD)
+        );
+        assert_eq!(0, above(slot(0, 5), BoardSize::STANDARD));
+    }
+
+    #[test]
+    fn splits_rows_by_parity() {
+        let column = |rows: &[u32], col: u32, stride: u32| {
+            rows.iter()
+                .fold(0u128, |mask, row| mask | 1 << (col * stride + row))
+        };
+        let board = |rows: &[u32], width: u32, stride: u32| {
+            (0..width).fold(0, |mask, col| mask | column(rows, col, stride))
+        };
+
+        let (odd, even) = row_parities(BoardSize::STANDARD);
+        assert_eq!(board(&[0, 2, 4], 7, 7), odd);
+        assert_eq!(board(&[1, 3, 5], 7, 7), even);
+        assert_eq!(0, even & BoardSize::STANDARD.bottom_mask());
+
+        let size = BoardSize::new(6, 5).unwrap();
+        let (odd, even) = row_parities(size);
+        assert_eq!(board(&[0, 2, 4], 6, 6), odd);
+        assert_eq!(board(&[1, 3], 6, 6), even);
+        assert_eq!(size.board_mask(), odd | even);
+    }
+
+    #[test]
+    fn counts_connect_fours_through_slots() {
+        // The standard board keeps its hand written table.
+        assert_eq!(CONNECT_FOUR_MAP, LINE_MAPS[1][3][2][..49]);
+        // Counting the lines of the standard board differs from it in the center column.
+        let counted = line_map(7, 6, 4);
+        assert_eq!([7, 10, 13, 13, 10, 7], counted[21..27]);
+        assert_eq!(CONNECT_FOUR_MAP[..21], counted[..21]);
+        // A 4x4 board only has the row, the column and one diagonal through its corners.
+        assert_eq!(3, LINE_MAPS[1][0][0][0]);
+        // Lines of five never fit across a 4x4 board, while lines of three fit more often.
+        assert_eq!(0, LINE_MAPS[2][0][0][0]);
+        assert_eq!(3, LINE_MAPS[0][0][0][0]);
+        assert_eq!(4, LINE_MAPS[0][0][0][1]);
+    }
+}
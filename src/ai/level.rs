@@ -0,0 +1,233 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::AtomicBool,
+    time::{Duration, Instant},
+};
+
+use rand::{Rng, seq::IteratorRandom};
+use thiserror::Error;
+
+use crate::game::{ConnectFourBoard, MAX_SLOTS, Move, Variant};
+
+use super::{Solver, TranspositionTable, book_move, deepen, strongest, try_solve_columns};
+
+// Solving is only quick enough to play against once a few discs are on the board.
+const PERFECT_PLAY_FROM: u16 = 12;
+
+/// Number of entries in the perfect level's solver table, which takes up 64MB. Solving gives
+/// up after the level's thinking time, long before a larger table would fill up.
+pub(super) const PERFECT_TABLE_SIZE: usize = 1 << 22;
+
+#[derive(Debug, Error)]
+#[error("Provided value was not a valid AI level.")]
+pub struct AiLevelConversionError;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum AiLevel {
+    Beginner,
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+    Perfect,
+}
+
+impl fmt::Display for AiLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for AiLevel {
+    type Err = AiLevelConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Beginner" => Ok(AiLevel::Beginner),
+            "Easy" => Ok(AiLevel::Easy),
+            "Medium" => Ok(AiLevel::Medium),
+            "Hard" => Ok(AiLevel::Hard),
+            "Perfect" => Ok(AiLevel::Perfect),
+            _ => Err(AiLevelConversionError),
+        }
+    }
+}
+
+impl AiLevel {
+    /// The following level, wrapping back around to the easiest.
+    pub const fn next(self) -> Self {
+        match self {
+            AiLevel::Beginner => AiLevel::Easy,
+            AiLevel::Easy => AiLevel::Medium,
+            AiLevel::Medium => AiLevel::Hard,
+            AiLevel::Hard => AiLevel::Perfect,
+            AiLevel::Perfect => AiLevel::Beginner,
+        }
+    }
+
+    /// Deepest the search is allowed to look ahead.
+    pub const fn depth(self) -> u8 {
+        match self {
+            AiLevel::Beginner => 2,
+            AiLevel::Easy => 4,
+            AiLevel::Medium => 8,
+            AiLevel::Hard | AiLevel::Perfect => MAX_SLOTS,
+        }
+    }
+
+    pub const fn thinking_time(self) -> Duration {
+        match self {
+            AiLevel::Beginner => Duration::from_millis(200),
+            AiLevel::Easy => Duration::from_millis(500),
+            AiLevel::Medium => Duration::from_millis(1000),
+            AiLevel::Hard => Duration::from_millis(1500),
+            AiLevel::Perfect => Duration::from_millis(3000),
+        }
+    }
+
+    /// Probability of playing a random weaker move instead of the best one found.
+    pub const fn mistake_chance(self) -> f64 {
+        match self {
+            AiLevel::Beginner => 0.35,
+            AiLevel::Easy => 0.2,
+            AiLevel::Medium => 0.05,
+            AiLevel::Hard | AiLevel::Perfect => 0.0,
+        }
+    }
+}
+
+/// Picks the move to play at the given level, or `None` when there is no legal move,
+/// searching on `threads` threads. Setting `stop` cuts the search short and plays the best
+/// move found so far.
+///
+/// The hard and perfect levels play the opening from the installed book, while the weaker
+/// levels search from the first move so they stay beatable.
+///
+/// The perfect level solves the position with `solver` once it has the time to, and falls
+/// back to searching when solving takes longer than its thinking time, when it is stopped or
+/// when there is no solver. The solver only knows the standard rules, so PopOut games are
+/// always searched.
+pub fn choose_move(
+    board: &ConnectFourBoard,
+    level: AiLevel,
+    table: &mut TranspositionTable,
+    solver: Option<&mut Solver>,
+    stop: &AtomicBool,
+    threads: usize,
+) -> Option<Move> {
+    if let Some(book_move) = opening_move(board, level) {
+        return Some(book_move);
+    }
+
+    let solved = match solver {
+        Some(solver)
+            if level == AiLevel::Perfect
+                && board.variant() == Variant::Standard
+                && board.move_count() >= PERFECT_PLAY_FROM =>
+        {
+            let deadline = Instant::now() + level.thinking_time();
+            try_solve_columns(solver, &mut board.clone(), Some(deadline), Some(stop))
+                .and_then(|scores| strongest(&scores, board.size()))
+                .map(|(column, _)| Move::Drop(column))
+        }
+        _ => None,
+    };
+    let best_move = solved.or_else(|| {
+        deepen(
+            board,
+            level.depth(),
+            level.thinking_time(),
+            table,
+            Some(stop),
+            threads,
+        )
+    });
+
+    with_mistakes(board, best_move, level.mistake_chance())
+}
+
+/// Move from the installed opening book, for the levels that play from it.
+pub(super) fn opening_move(board: &ConnectFourBoard, level: AiLevel) -> Option<Move> {
+    match level {
+        AiLevel::Hard | AiLevel::Perfect => book_move(board),
+        AiLevel::Beginner | AiLevel::Easy | AiLevel::Medium => None,
+    }
+}
+
+/// Swaps `best_move` for a random weaker one with a probability of `mistake_chance`.
+pub(super) fn with_mistakes(
+    board: &ConnectFourBoard,
+    best_move: Option<Move>,
+    mistake_chance: f64,
+) -> Option<Move> {
+    let mut rng = rand::rng();
+    if rng.random_bool(mistake_chance) {
+        let weaker_move = board
+            .legal_moves()
+            .into_iter()
+            .filter(|&legal| Some(legal) != best_move)
+            .choose(&mut rng);
+        if weaker_move.is_some() {
+            return weaker_move;
+        }
+    }
+
+    best_move
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::AtomicBool, time::Instant};
+
+    use crate::{
+        ai::{Solver, TranspositionTable},
+        game::{Column, ConnectFourBoard, Move, test_positions::double_threat},
+    };
+
+    use super::{AiLevel, choose_move};
+
+    #[test]
+    fn strongest_levels_take_immediate_win() {
+        let board = double_threat();
+        for level in [AiLevel::Hard, AiLevel::Perfect] {
+            let mut table = TranspositionTable::new(1024);
+            let mut solver = Solver::new(TranspositionTable::new(1024));
+            let stop = AtomicBool::new(false);
+            assert_eq!(
+                Some(Move::Drop(Column::One)),
+                choose_move(&board, level, &mut table, Some(&mut solver), &stop, 1)
+            );
+        }
+    }
+
+    #[test]
+    fn perfect_level_stops_solving() {
+        // Far too early in the game to solve within the thinking time.
+        let board = ConnectFourBoard::from_moves("123456776543").unwrap();
+        let mut table = TranspositionTable::new(1024);
+        let mut solver = Solver::new(TranspositionTable::new(1024));
+        let stop = AtomicBool::new(true);
+
+        let start = Instant::now();
+        let next_move = choose_move(
+            &board,
+            AiLevel::Perfect,
+            &mut table,
+            Some(&mut solver),
+            &stop,
+            2,
+        );
+        assert!(next_move.is_some_and(|next_move| board.is_legal(next_move)));
+        assert!(start.elapsed() < AiLevel::Perfect.thinking_time());
+    }
+
+    #[test]
+    fn levels_cycle_back_to_beginner() {
+        let mut level = AiLevel::Beginner;
+        for _ in 0..5 {
+            level = level.next();
+        }
+        assert_eq!(AiLevel::Beginner, level);
+    }
+}
@@ -0,0 +1,667 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::game::{
+    BoardSize, BoardStatus, Column, ConnectFourBoard, MAX_SLOTS, Move, Player, Variant,
+};
+
+use analysis::{solve_columns, strongest, try_solve_columns};
+use table::{Bound, Entry};
+
+pub use analysis::{SearchLimits, analyze, best_move};
+pub use bench::{BenchReport, bench};
+pub use book::{Book, BookError};
+pub use eval::{EvalWeights, Evaluation, Evaluator, ThreatEvaluator, UnknownEvaluation};
+pub use level::{AiLevel, AiLevelConversionError, choose_move};
+pub use score::Score;
+pub use solver::{Solver, SolverError, solve};
+pub use strategy::{
+    GreedyStrategy, LevelStrategy, MinimaxStrategy, RandomStrategy, SolverStrategy, StopHandle,
+    Strategy,
+};
+pub use table::TranspositionTable;
+
+mod analysis;
+mod bench;
+mod book;
+mod eval;
+mod level;
+mod score;
+mod solver;
+mod strategy;
+mod table;
+
+const INFINITY: i32 = 1000000;
+const WIN_SCORE: i32 = 100000;
+
+/// Columns of a board `width` columns wide from the center outwards. Columns closer to the
+/// center take part in more lines, so they are searched first.
+fn move_order(width: u8) -> impl DoubleEndedIterator<Item = Column> {
+    let center = width / 2;
+    (0..width).filter_map(move |i| {
+        let idx = if i % 2 == 1 {
+            center - i.div_ceil(2)
+        } else {
+            center + i / 2
+        };
+        Column::try_from(idx + 1).ok()
+    })
+}
+
+pub fn next_move(
+    board: &ConnectFourBoard,
+    depth: u8,
+    table: &mut TranspositionTable,
+) -> Option<Move> {
+    if let Some(book_move) = book_move(board) {
+        return Some(book_move);
+    }
+    let mut board = board.clone();
+    let mut search = Search::new(table, None, None);
+    search
+        .root(&mut board, depth.max(1), None)
+        .map(|(next_move, _)| next_move)
+}
+
+/// Root search to `depth` of the main thread with `threads - 1` helpers, along with the
+/// number of positions every thread visited.
+fn parallel_root(
+    board: &ConnectFourBoard,
+    depth: u8,
+    table: &TranspositionTable,
+    evaluator: &dyn Evaluator,
+    threads: usize,
+) -> (Option<(Move, i32)>, u64) {
+    let (best, nodes) = with_helpers(board, depth, None, table, evaluator, threads, || {
+        let mut search = Search::new(table, None, None);
+        search.evaluator = evaluator;
+        let best = search.root(&mut board.clone(), depth, None);
+        (best, search.nodes)
+    });
+    (best.0, best.1 + nodes)
+}
+
+/// Runs `main` on this thread while `threads - 1` helpers search the same position and
+/// share `table` with it, along with the number of positions the helpers visited.
+///
+/// This is Lazy SMP: the helpers search on their own and only help through the entries
+/// they leave in the table, which lets the main search skip or reorder much of its work.
+/// Those entries can also change which move the main search settles on, so results may
+/// differ from a search on one thread. Helpers only pay off with a core of their own each,
+/// which is why searches run on one thread unless asked otherwise. Helpers give up at
+/// `deadline` or once `main` returns.
+fn with_helpers<T>(
+    board: &ConnectFourBoard,
+    max_depth: u8,
+    deadline: Option<Instant>,
+    table: &TranspositionTable,
+    evaluator: &dyn Evaluator,
+    threads: usize,
+    main: impl FnOnce() -> T,
+) -> (T, u64) {
+    let done = AtomicBool::new(false);
+    let nodes = AtomicU64::new(0);
+    let result = thread::scope(|scope| {
+        for helper in 1..threads {
+            let (done, nodes) = (&done, &nodes);
+            let mut board = board.clone();
+            scope.spawn(move || {
+                let mut search = Search::new(table, deadline, Some(done));
+                search.evaluator = evaluator;
+                // Every other helper looks a ply further ahead, so the threads spread out
+                // over different parts of the tree instead of all repeating the same work.
+                for depth in 1..=max_depth + (helper % 2) as u8 {
+                    search.root(&mut board, depth, None);
+                    if search.stopped {
+                        break;
+                    }
+                }
+                nodes.fetch_add(search.nodes, Ordering::Relaxed);
+            });
+        }
+
+        let result = main();
+        done.store(true, Ordering::Relaxed);
+        result
+    });
+    (result, nodes.into_inner())
+}
+
+/// Deepens the search one ply at a time until `thinking_time` runs out, returning the
+/// best move of the deepest completed iteration.
+pub fn next_move_timed(
+    board: &ConnectFourBoard,
+    thinking_time: Duration,
+    table: &mut TranspositionTable,
+) -> Option<Move> {
+    if let Some(book_move) = book_move(board) {
+        return Some(book_move);
+    }
+    deepen(board, MAX_SLOTS, thinking_time, table, None, 1)
+}
+
+/// Iterative deepening search on `threads` threads that also gives up once `stop` is set.
+fn deepen(
+    board: &ConnectFourBoard,
+    max_depth: u8,
+    thinking_time: Duration,
+    table: &mut TranspositionTable,
+    stop: Option<&AtomicBool>,
+    threads: usize,
+) -> Option<Move> {
+    think(
+        board,
+        max_depth,
+        Some(thinking_time),
+        table,
+        stop,
+        threads,
+        |_| {},
+    )
+}
+
+/// Solved move from the installed opening book, if it covers the position.
+fn book_move(board: &ConnectFourBoard) -> Option<Move> {
+    let (column, _) = book::installed()?.best_move(board)?;
+    Some(Move::Drop(column))
+}
+
+/// Progress of an iterative deepening search, reported after every completed depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub score: Score,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// Principal variation, the line of play the search expects from the position.
+    pub pv: Vec<Move>,
+}
+
+/// Iterative deepening search that calls `report` after every completed depth. Without a
+/// thinking time, it runs until `max_depth` is reached, a forced result is found or `stop`
+/// is set.
+///
+/// With more than one thread, helper threads search the same position alongside it and
+/// share `table`, see `with_helpers`. Reported node counts only cover the thread that picks
+/// the move.
+pub fn think(
+    board: &ConnectFourBoard,
+    max_depth: u8,
+    thinking_time: Option<Duration>,
+    table: &mut TranspositionTable,
+    stop: Option<&AtomicBool>,
+    threads: usize,
+    report: impl FnMut(&SearchInfo),
+) -> Option<Move> {
+    if board.status() != BoardStatus::OnGoing {
+        return None;
+    }
+
+    let max_depth = max_depth.min(board.moves_left().unwrap_or(MAX_SLOTS));
+    let start = Instant::now();
+    let deadline = thinking_time.map(|time| start + time);
+    let table = &*table;
+    let (best_move, _) = with_helpers(
+        board,
+        max_depth,
+        deadline,
+        table,
+        &EvalWeights::DEFAULT,
+        threads,
+        || {
+            deepen_on(
+                board,
+                max_depth,
+                start,
+                Search::new(table, deadline, stop),
+                report,
+            )
+        },
+    );
+    best_move
+}
+
+/// Main thread of `think`.
+fn deepen_on(
+    board: &ConnectFourBoard,
+    max_depth: u8,
+    start: Instant,
+    mut search: Search,
+    mut report: impl FnMut(&SearchInfo),
+) -> Option<Move> {
+    let mut board = board.clone();
+
+    let mut best_move = None;
+    for depth in 1..=max_depth {
+        match search.root(&mut board, depth, best_move) {
+            Some((next_move, score)) if !search.stopped => {
+                best_move = Some(next_move);
+                report(&SearchInfo {
+                    depth,
+                    score: Score::from_search(score, board.move_count()),
+                    nodes: search.nodes,
+                    elapsed: start.elapsed(),
+                    pv: search.principal_variation(&mut board, next_move, depth),
+                });
+                // A forced result will not change by searching any deeper.
+                if score.abs() >= WIN_SCORE - MAX_SLOTS as i32 {
+                    break;
+                }
+            }
+            // Even the shallowest search ran out of time, so settle for its partial result.
+            partial if best_move.is_none() => best_move = partial.map(|(next_move, _)| next_move),
+            _ => break,
+        }
+    }
+
+    best_move
+}
+
+struct Search<'a> {
+    table: &'a TranspositionTable,
+    evaluator: &'a dyn Evaluator,
+    deadline: Option<Instant>,
+    stop: Option<&'a AtomicBool>,
+    nodes: u64,
+    stopped: bool,
+}
+
+impl<'a> Search<'a> {
+    fn new(
+        table: &'a TranspositionTable,
+        deadline: Option<Instant>,
+        stop: Option<&'a AtomicBool>,
+    ) -> Self {
+        Self {
+            table,
+            evaluator: &EvalWeights::DEFAULT,
+            deadline,
+            stop,
+            nodes: 0,
+            stopped: false,
+        }
+    }
+
+    fn should_stop(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn root(
+        &mut self,
+        board: &mut ConnectFourBoard,
+        depth: u8,
+        first_move: Option<Move>,
+    ) -> Option<(Move, i32)> {
+        let mut alpha = -INFINITY;
+        let beta = INFINITY;
+
+        let hash_move = first_move.or_else(|| {
+            self.table
+                .get(board.key())
+                .and_then(|entry| entry.best_move)
+        });
+        // Wins on the spot are played right away, and moves that let the opponent win are
+        // only searched when every move does. Pops can win for either player in PopOut, so
+        // there only winning drops are singled out.
+        let winning = board.winning_moves(board.current_player());
+        let moves = match board.non_losing_moves() {
+            _ if winning != 0 => winning,
+            _ if board.variant() == Variant::PopOut => board.playable_moves(),
+            0 => board.playable_moves(),
+            non_losing => non_losing,
+        };
+        let mut best_move = None;
+        for next_move in ordered_moves(hash_move, board.size(), board.variant()) {
+            let searched = match next_move {
+                Move::Drop(column) => is_among(column, moves, board.size()),
+                Move::Pop(column) => winning == 0 && board.can_pop(column),
+            };
+            if !searched {
+                continue;
+            }
+
+            let _ = board.try_play(next_move);
+            let score = -self.negamax(board, depth - 1, -beta, -alpha);
+            board.pop_move();
+
+            if self.stopped {
+                break;
+            }
+            if score > alpha || best_move.is_none() {
+                alpha = score;
+                best_move = Some(next_move);
+            }
+        }
+
+        if !self.stopped {
+            self.table.store(Entry {
+                key: board.key(),
+                depth,
+                score: alpha,
+                bound: Bound::Exact,
+                best_move,
+            });
+        }
+
+        best_move.map(|next_move| (next_move, alpha))
+    }
+
+    /// Follows the best moves stored in the table from `first_move` onwards, for at most
+    /// `depth` plies.
+    fn principal_variation(
+        &self,
+        board: &mut ConnectFourBoard,
+        first_move: Move,
+        depth: u8,
+    ) -> Vec<Move> {
+        let mut pv = Vec::with_capacity(depth as usize);
+        let mut next_move = Some(first_move);
+        while let Some(pv_move) = next_move {
+            if pv.len() == depth as usize || board.try_play(pv_move).is_err() {
+                break;
+            }
+            pv.push(pv_move);
+            next_move = self
+                .table
+                .get(board.key())
+                .and_then(|entry| entry.best_move);
+        }
+        for _ in &pv {
+            board.pop_move();
+        }
+        pv
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut ConnectFourBoard,
+        depth: u8,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.should_stop() {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return 0;
+        }
+
+        if depth == 0 || board.status() != BoardStatus::OnGoing {
+            return sign_by_player(board.current_player()) * evalulate_board(board, self.evaluator);
+        }
+
+        // A win on the spot needs no search, and neither do moves that let the opponent win.
+        // Pops can still save a PopOut game that looks lost, so there every move is searched.
+        let moves_played = board.move_count() as i32;
+        if board.winning_moves(board.current_player()) != 0 {
+            return WIN_SCORE - moves_played - 1;
+        }
+        let drops = match board.variant() {
+            Variant::Standard => match board.non_losing_moves() {
+                0 => return -(WIN_SCORE - moves_played - 2),
+                non_losing => non_losing,
+            },
+            Variant::PopOut => board.playable_moves(),
+        };
+
+        let key = board.key();
+        let original_alpha = alpha;
+        let mut hash_move = None;
+        if let Some(entry) = self.table.get(key) {
+            hash_move = entry.best_move;
+            if entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower => alpha = alpha.max(entry.score),
+                    Bound::Upper => beta = beta.min(entry.score),
+                }
+                if alpha >= beta {
+                    return entry.score;
+                }
+            }
+        }
+
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for next_move in ordered_moves(hash_move, board.size(), board.variant()) {
+            let searched = match next_move {
+                Move::Drop(column) => is_among(column, drops, board.size()),
+                Move::Pop(column) => board.can_pop(column),
+            };
+            if !searched {
+                continue;
+            }
+
+            let _ = board.try_play(next_move);
+            let score = -self.negamax(board, depth - 1, -beta, -alpha);
+            board.pop_move();
+
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(next_move);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.store(Entry {
+            key,
+            depth,
+            score: best_score,
+            bound,
+            best_move,
+        });
+
+        best_score
+    }
+}
+
+/// Search order for a position, trying the move suggested by the transposition table first.
+/// Pops are tried after every drop, and only in PopOut games.
+fn ordered_moves(
+    hash_move: Option<Move>,
+    size: BoardSize,
+    variant: Variant,
+) -> impl Iterator<Item = Move> {
+    let pops = match variant {
+        Variant::Standard => None,
+        Variant::PopOut => Some(move_order(size.width()).map(Move::Pop)),
+    };
+    hash_move.into_iter().chain(
+        move_order(size.width())
+            .map(Move::Drop)
+            .chain(pops.into_iter().flatten())
+            .filter(move |&m| Some(m) != hash_move),
+    )
+}
+
+/// Whether `moves` holds a slot in `column`.
+fn is_among(column: Column, moves: u128, size: BoardSize) -> bool {
+    moves & size.column_mask(column) != 0
+}
+
+fn sign_by_player(player: Player) -> i32 {
+    match player {
+        Player::One => 1,
+        Player::Two => -1,
+    }
+}
+
+fn evalulate_board(board: &ConnectFourBoard, evaluator: &dyn Evaluator) -> i32 {
+    match board.status() {
+        // Quicker wins are preferred over slower ones.
+        BoardStatus::Winner(player) => {
+            let score = WIN_SCORE - board.move_count() as i32;
+            match player {
+                Player::One => score,
+                Player::Two => -score,
+            }
+        }
+        BoardStatus::Draw => 0,
+        BoardStatus::OnGoing => evaluator.evaluate(board),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{BoardSize, Column, ConnectFourBoard, Move, Rules, Variant};
+
+    use std::time::Duration;
+
+    use super::{
+        EvalWeights, Score, Search, TranspositionTable, next_move, next_move_timed, parallel_root,
+        think,
+    };
+
+    fn board_from(columns: &[Column]) -> ConnectFourBoard {
+        let mut board = ConnectFourBoard::default();
+        for &column in columns {
+            board.try_move(column).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn takes_immediate_win() {
+        let board = board_from(&[
+            Column::One,
+            Column::Two,
+            Column::One,
+            Column::Two,
+            Column::One,
+            Column::Two,
+        ]);
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(
+            Some(Move::Drop(Column::One)),
+            next_move(&board, 6, &mut table)
+        );
+    }
+
+    #[test]
+    fn blocks_opponent_win() {
+        let board = board_from(&[
+            Column::One,
+            Column::Two,
+            Column::Seven,
+            Column::Two,
+            Column::Seven,
+            Column::Two,
+        ]);
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(
+            Some(Move::Drop(Column::Two)),
+            next_move(&board, 6, &mut table)
+        );
+    }
+
+    #[test]
+    fn timed_search_returns_move_when_out_of_time() {
+        let board = ConnectFourBoard::default();
+        let mut table = TranspositionTable::new(1024);
+        assert!(next_move_timed(&board, Duration::ZERO, &mut table).is_some());
+    }
+
+    #[test]
+    fn timed_search_finds_immediate_win() {
+        let board = board_from(&[
+            Column::Four,
+            Column::Four,
+            Column::Five,
+            Column::Five,
+            Column::Six,
+            Column::Six,
+        ]);
+        let mut table = TranspositionTable::new(1024);
+        let mv = next_move_timed(&board, Duration::from_millis(200), &mut table);
+        assert!(matches!(
+            mv,
+            Some(Move::Drop(Column::Three | Column::Seven))
+        ));
+    }
+
+    #[test]
+    fn pops_to_win_in_popout() {
+        // Player two threatens the fifth column, but popping the first column wins first.
+        let mut board = ConnectFourBoard::with_rules(
+            BoardSize::STANDARD,
+            Rules::new(Variant::PopOut, 4).unwrap(),
+        );
+        board.play_moves("1112233447").unwrap();
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(
+            Some(Move::Pop(Column::One)),
+            next_move(&board, 4, &mut table)
+        );
+
+        let board = ConnectFourBoard::from_moves("1112233447").unwrap();
+        assert_eq!(
+            Some(Move::Drop(Column::Five)),
+            next_move(&board, 4, &mut table)
+        );
+    }
+
+    #[test]
+    fn blocks_longer_lines() {
+        // Four in a row has not won yet, so player two has to block the fifth disc.
+        let rules = Rules::new(Variant::Standard, 5).unwrap();
+        let mut board = ConnectFourBoard::with_rules(BoardSize::STANDARD, rules);
+        board.play_moves("1121314").unwrap();
+        let mut table = TranspositionTable::new(1024);
+        assert_eq!(
+            Some(Move::Drop(Column::Five)),
+            next_move(&board, 6, &mut table)
+        );
+    }
+
+    #[test]
+    fn parallel_search_agrees_with_single_thread() {
+        // Forced results within the search depth score the same however the threads
+        // interleave.
+        for moves in ["4455", "44556", "121212535363", "3424536"] {
+            let board = ConnectFourBoard::from_moves(moves).unwrap();
+            let table = TranspositionTable::new(1 << 16);
+            let mut search = Search::new(&table, None, None);
+            let single = search.root(&mut board.clone(), 8, None);
+
+            let table = TranspositionTable::new(1 << 16);
+            let (parallel, _) = parallel_root(&board, 8, &table, &EvalWeights::DEFAULT, 4);
+            assert_eq!(
+                single.map(|(_, score)| score),
+                parallel.map(|(_, score)| score)
+            );
+        }
+
+        let board = ConnectFourBoard::from_moves("4455").unwrap();
+        let mut table = TranspositionTable::new(1 << 16);
+        let mut scores = Vec::new();
+        let best_move = think(&board, 6, None, &mut table, None, 3, |info| {
+            scores.push(info.score)
+        });
+        assert!(matches!(
+            best_move,
+            Some(Move::Drop(Column::Three | Column::Six))
+        ));
+        // Finding the forced win ends the search before the depth limit.
+        assert!(scores.len() < 6);
+        assert_eq!(Some(&Score::Win(2)), scores.last());
+    }
+}
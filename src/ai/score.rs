@@ -0,0 +1,65 @@
+use std::fmt;
+
+use crate::game::MAX_SLOTS;
+
+/// Value of a position from the point of view of the player to move.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Score {
+    /// The player to move wins with their Nth move.
+    Win(u8),
+    /// The opponent wins with their Nth move.
+    Loss(u8),
+    Draw,
+    /// Heuristic evaluation where no forced result was found. Positive values favor the
+    /// player to move.
+    Eval(i32),
+}
+
+impl Score {
+    /// Converts a negamax search score of a position with `move_count` moves played.
+    pub(super) fn from_search(score: i32, move_count: u16) -> Self {
+        let move_count = move_count as i32;
+        if score.abs() < super::WIN_SCORE - MAX_SLOTS as i32 {
+            return Score::Eval(score);
+        }
+
+        // Win scores are offset by the number of discs on the board once the game is won.
+        let final_count = super::WIN_SCORE - score.abs();
+        if score > 0 {
+            Score::Win(((final_count - move_count + 1) / 2) as u8)
+        } else {
+            Score::Loss(((final_count - move_count) / 2) as u8)
+        }
+    }
+
+    /// Orders scores from worst to best for the player to move.
+    pub(super) fn rank(self) -> i32 {
+        match self {
+            Score::Win(moves) => 2 * super::WIN_SCORE - moves as i32,
+            Score::Loss(moves) => -2 * super::WIN_SCORE + moves as i32,
+            Score::Draw => 0,
+            Score::Eval(score) => score,
+        }
+    }
+
+    /// Score of the position before the last move, as seen by the player who made it.
+    pub(super) fn previous(self) -> Self {
+        match self {
+            Score::Win(moves) => Score::Loss(moves),
+            Score::Loss(moves) => Score::Win(moves + 1),
+            Score::Draw => Score::Draw,
+            Score::Eval(score) => Score::Eval(-score),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Win(moves) => write!(f, "mate in {}", moves),
+            Score::Loss(moves) => write!(f, "mated in {}", moves),
+            Score::Draw => write!(f, "draw"),
+            Score::Eval(score) => write!(f, "{:+}", score),
+        }
+    }
+}
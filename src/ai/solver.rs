@@ -0,0 +1,504 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+use thiserror::Error;
+
+use crate::game::{
+    Bitboard, BoardSize, BoardStatus, ConnectFourBoard, MAX_WIDTH, Player, Rules, Variant,
+    line_completions, mirror, pack_key,
+};
+
+use super::{
+    Score, move_order,
+    table::{Bound, Entry, TranspositionTable},
+};
+
+/// Number of entries allocated by `Solver::default`, which takes up 256MB. Early positions
+/// need a table this large to be solved in minutes rather than hours, while later ones do
+/// with far less, see `Solver::for_board`. With it, the empty standard board takes about
+/// 1.5 billion positions and 8 minutes on one core.
+pub const SOLVER_TABLE_SIZE: usize = 1 << 24;
+
+/// Fewest entries `Solver::for_board` allocates, enough for positions close to the end.
+const MIN_TABLE_SIZE: usize = 1 << 12;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SolverError {
+    #[error("Only positions played by the standard rules can be solved.")]
+    UnsupportedVariant,
+}
+
+/// Perfect play solver using a null window negamax search.
+///
+/// Internally positions are scored by how early the game is won: a positive score is the
+/// number of discs the player to move still holds when they complete a line, a negative score
+/// is the same for the opponent and zero is a draw.
+pub struct Solver {
+    table: TranspositionTable,
+    nodes: u64,
+    stopped: bool,
+}
+
+/// When a solve started by `Solver::try_solve` has to give up.
+#[derive(Copy, Clone)]
+struct Limits<'a> {
+    deadline: Option<Instant>,
+    stop: Option<&'a AtomicBool>,
+}
+
+impl Limits<'_> {
+    fn reached(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+}
+
+impl Solver {
+    pub fn new(table: TranspositionTable) -> Self {
+        Self {
+            table,
+            nodes: 0,
+            stopped: false,
+        }
+    }
+
+    /// Solver with a table sized for solving `board` and the positions that follow it.
+    /// Every empty slot makes a solve visit a few times as many positions, so the table
+    /// doubles with each one beyond 20 from `MIN_TABLE_SIZE` up to `SOLVER_TABLE_SIZE`.
+    pub fn for_board(board: &ConnectFourBoard) -> Self {
+        let empty = board.size().slots() as usize - board.move_count() as usize;
+        let size = (MIN_TABLE_SIZE << empty.saturating_sub(20)).min(SOLVER_TABLE_SIZE);
+        Self::new(TranspositionTable::new(size))
+    }
+
+    /// Number of positions visited since the solver was created.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Solves the position, which has to be played by the standard rules.
+    pub fn solve(&mut self, board: &ConnectFourBoard) -> Result<Score, SolverError> {
+        // Without limits, the only positions given up on are those of other rules.
+        self.try_solve(board, None, None)
+            .ok_or(SolverError::UnsupportedVariant)
+    }
+
+    /// Solves like `solve`, but gives up with `None` once `deadline` passes or `stop` is set,
+    /// as well as on positions played by other rules than the standard ones. Positions
+    /// solved before giving up stay in the table and speed up the next attempt.
+    pub fn try_solve(
+        &mut self,
+        board: &ConnectFourBoard,
+        deadline: Option<Instant>,
+        stop: Option<&AtomicBool>,
+    ) -> Option<Score> {
+        if board.variant() != Variant::Standard {
+            return None;
+        }
+        match board.status() {
+            BoardStatus::Winner(_) => return Some(Score::Loss(0)),
+            BoardStatus::Draw => return Some(Score::Draw),
+            BoardStatus::OnGoing => {}
+        }
+
+        self.stopped = false;
+        let limits = Limits { deadline, stop };
+        let score = if board.size().bits() <= u64::BITS {
+            self.solve_position(&Position::<u64>::from(board), limits)
+        } else {
+            self.solve_position(&Position::<u128>::from(board), limits)
+        };
+        let slots = board.size().slots() as i32;
+        (!self.stopped).then(|| to_score(score, board.move_count() as i32, slots))
+    }
+
+    fn solve_position<B: Bitboard>(&mut self, position: &Position<B>, limits: Limits) -> i32 {
+        let slots = position.slots();
+        if position.can_win_next() {
+            return (slots + 1 - position.moves) / 2;
+        }
+
+        let mut min = -(slots - position.moves) / 2;
+        let mut max = (slots + 1 - position.moves) / 2;
+        while min < max {
+            // Null window searches closer to zero resolve quicker, so the window is
+            // biased towards it rather than being a plain binary search.
+            let mut mid = min + (max - min) / 2;
+            if mid <= 0 && min / 2 < mid {
+                mid = min / 2;
+            } else if mid >= 0 && max / 2 > mid {
+                mid = max / 2;
+            }
+
+            let score = self.negamax(position, mid, mid + 1, limits);
+            if self.stopped {
+                break;
+            }
+            if score <= mid {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+        min
+    }
+
+    // Assumes the player to move can not win with their next move. Once the limits are
+    // reached, every call returns right away without storing anything in the table.
+    fn negamax<B: Bitboard>(
+        &mut self,
+        position: &Position<B>,
+        mut alpha: i32,
+        mut beta: i32,
+        limits: Limits,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(4096) && limits.reached() {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return 0;
+        }
+
+        let slots = position.slots();
+        let possible = position.possible_non_losing_moves();
+        if possible == B::EMPTY {
+            return -(slots - position.moves) / 2;
+        }
+        if position.moves >= slots - 2 {
+            return 0;
+        }
+
+        let min = -(slots - 2 - position.moves) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let mut max = (slots - 1 - position.moves) / 2;
+        let key = position.key();
+        if let Some(entry) = self.table.get(key) {
+            match entry.bound {
+                Bound::Lower if alpha < entry.score => {
+                    alpha = entry.score;
+                    if alpha >= beta {
+                        return alpha;
+                    }
+                }
+                Bound::Upper => max = entry.score,
+                _ => {}
+            }
+        }
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        for candidate in position.sorted_moves(possible) {
+            let mut next = *position;
+            next.play(candidate);
+            let score = -self.negamax(&next, -beta, -alpha, limits);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                self.store(key, score, Bound::Lower);
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        self.store(key, alpha, Bound::Upper);
+        alpha
+    }
+
+    fn store(&mut self, key: u64, score: i32, bound: Bound) {
+        self.table.store(Entry {
+            key,
+            depth: 0,
+            score,
+            bound,
+            best_move: None,
+        });
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new(TranspositionTable::new(SOLVER_TABLE_SIZE))
+    }
+}
+
+/// Convenience wrapper solving a single position with a fresh `Solver`.
+pub fn solve(board: &ConnectFourBoard) -> Result<Score, SolverError> {
+    Solver::for_board(board).solve(board)
+}
+
+fn to_score(score: i32, moves: i32, slots: i32) -> Score {
+    match score {
+        0 => Score::Draw,
+        score if score > 0 => Score::Win(((slots + 1 - moves) / 2 - score + 1) as u8),
+        score => Score::Loss(((slots - moves) / 2 + score + 1) as u8),
+    }
+}
+
+/// Compact copy of a board that only tracks what the solver needs to make moves quickly.
+/// Boards whose columns fit in 64 bits are copied into `u64` bitboards.
+#[derive(Copy, Clone)]
+struct Position<B> {
+    current: B,
+    mask: B,
+    moves: i32,
+    size: BoardSize,
+    rules: Rules,
+    bottom: B,
+    board: B,
+}
+
+impl<B: Bitboard> From<&ConnectFourBoard> for Position<B> {
+    fn from(board: &ConnectFourBoard) -> Self {
+        let current = match board.current_player() {
+            Player::One => board.player_one_bitboard(),
+            Player::Two => board.player_two_bitboard(),
+        };
+        Self {
+            current: B::truncate(current),
+            mask: B::truncate(board.player_one_bitboard() | board.player_two_bitboard()),
+            moves: board.move_count() as i32,
+            size: board.size(),
+            rules: board.rules(),
+            bottom: B::truncate(board.size().bottom_mask()),
+            board: B::truncate(board.size().board_mask()),
+        }
+    }
+}
+
+impl<B: Bitboard> Position<B> {
+    fn slots(&self) -> i32 {
+        self.size.slots() as i32
+    }
+
+    // Mirrored positions have the same score, so both share the smaller of their keys.
+    fn key(&self) -> u64 {
+        let key = self.current + self.mask;
+        let key = key.min(mirror(key, self.size));
+        pack_key(key.widen(), self.size, self.rules)
+    }
+
+    fn play(&mut self, position: B) {
+        self.current = self.current ^ self.mask;
+        self.mask |= position;
+        self.moves += 1;
+    }
+
+    fn can_win_next(&self) -> bool {
+        self.winning_position() & self.possible() != B::EMPTY
+    }
+
+    fn possible(&self) -> B {
+        (self.mask + self.bottom) & self.board
+    }
+
+    /// Moves that do not hand the opponent an immediate win.
+    fn possible_non_losing_moves(&self) -> B {
+        let mut possible = self.possible();
+        let opponent_wins = self.opponent_winning_position();
+        let forced = possible & opponent_wins;
+        if forced != B::EMPTY {
+            // Two open threats can not both be blocked.
+            if forced & (forced - B::truncate(1)) != B::EMPTY {
+                return B::EMPTY;
+            }
+            possible = forced;
+        }
+        possible & !(opponent_wins >> 1)
+    }
+
+    /// Orders the moves in `possible` by how many new threats they create.
+    fn sorted_moves(&self, possible: B) -> MoveSorter<B> {
+        let mut sorter = MoveSorter::default();
+        for column in move_order(self.size.width()).rev() {
+            let candidate = possible & B::truncate(self.size.column_mask(column));
+            if candidate != B::EMPTY {
+                sorter.add(candidate, self.move_score(candidate));
+            }
+        }
+        sorter
+    }
+
+    fn move_score(&self, candidate: B) -> u32 {
+        self.winning_slots(self.current | candidate).count_ones()
+    }
+
+    fn winning_position(&self) -> B {
+        self.winning_slots(self.current)
+    }
+
+    fn opponent_winning_position(&self) -> B {
+        self.winning_slots(self.current ^ self.mask)
+    }
+
+    /// Empty slots that complete a line for the discs in `position`.
+    fn winning_slots(&self, position: B) -> B {
+        let completions = line_completions(position, self.size.stride(), self.rules.connect());
+        completions & (self.board ^ self.mask)
+    }
+}
+
+/// Insertion sorted list of moves that yields the highest scoring move first. Moves with
+/// equal scores are yielded in the reverse order they were added in.
+#[derive(Default)]
+struct MoveSorter<B> {
+    entries: [(B, u32); MAX_WIDTH as usize],
+    len: usize,
+}
+
+impl<B: Bitboard> MoveSorter<B> {
+    fn add(&mut self, candidate: B, score: u32) {
+        let mut pos = self.len;
+        while pos > 0 && self.entries[pos - 1].1 > score {
+            self.entries[pos] = self.entries[pos - 1];
+            pos -= 1;
+        }
+        self.entries[pos] = (candidate, score);
+        self.len += 1;
+    }
+}
+
+impl<B: Bitboard> Iterator for MoveSorter<B> {
+    type Item = B;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(self.entries[self.len].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use crate::game::{BoardSize, BoardStatus, Column, ConnectFourBoard, Rules, Variant};
+
+    use super::{Limits, Position, Score, Solver, SolverError, TranspositionTable};
+
+    fn solver() -> Solver {
+        Solver::new(TranspositionTable::new(1 << 16))
+    }
+
+    fn board_from(moves: &str) -> ConnectFourBoard {
+        ConnectFourBoard::from_moves(moves).unwrap()
+    }
+
+    #[test]
+    fn solves_immediate_win() {
+        assert_eq!(Ok(Score::Win(1)), solver().solve(&board_from("121212")));
+    }
+
+    #[test]
+    fn solves_unstoppable_double_threat() {
+        // Player one can open both ends of three in a row on the bottom row.
+        assert_eq!(Ok(Score::Win(2)), solver().solve(&board_from("4455")));
+    }
+
+    #[test]
+    fn solves_forced_loss() {
+        assert_eq!(Ok(Score::Loss(1)), solver().solve(&board_from("44556")));
+    }
+
+    #[test]
+    fn solves_shorter_lines() {
+        // Two discs next to each other already threaten both ends of a line of three.
+        let rules = Rules::new(Variant::Standard, 3).unwrap();
+        let mut board = ConnectFourBoard::with_rules(BoardSize::STANDARD, rules);
+        board.play_moves("44").unwrap();
+        assert_eq!(Ok(Score::Win(2)), solver().solve(&board));
+    }
+
+    #[test]
+    fn solves_concluded_board() {
+        assert_eq!(Ok(Score::Loss(0)), solver().solve(&board_from("1212121")));
+    }
+
+    #[test]
+    fn refuses_popout_positions() {
+        let rules = Rules::new(Variant::PopOut, 4).unwrap();
+        let board = ConnectFourBoard::with_rules(BoardSize::STANDARD, rules);
+        assert_eq!(Err(SolverError::UnsupportedVariant), solver().solve(&board));
+    }
+
+    #[test]
+    fn gives_up_once_stopped() {
+        let mut solver = solver();
+        let stop = AtomicBool::new(true);
+        assert_eq!(
+            None,
+            solver.try_solve(&ConnectFourBoard::default(), None, Some(&stop))
+        );
+        // Giving up leaves nothing wrong behind in the table.
+        assert_eq!(Ok(Score::Loss(1)), solver.solve(&board_from("44556")));
+        assert_eq!(
+            Some(Score::Win(1)),
+            solver.try_solve(&board_from("121212"), None, Some(&stop))
+        );
+    }
+
+    #[test]
+    fn agrees_with_exhaustive_search() {
+        let mut rng = StdRng::seed_from_u64(4);
+        // Boards that fit in 64 bits are solved on narrower bitboards, so check both.
+        let (mut narrow, mut wide) = (solver(), solver());
+        let mut checked = 0;
+        while checked < 20 {
+            let mut board = ConnectFourBoard::default();
+            while board.move_count() < 30 && board.status() == BoardStatus::OnGoing {
+                let column = Column::try_from(rng.random_range(1..=7)).unwrap();
+                let _ = board.try_move(column);
+            }
+            if board.status() != BoardStatus::OnGoing {
+                continue;
+            }
+
+            let expected = exhaustive_score(&mut board);
+            let limits = Limits {
+                deadline: None,
+                stop: None,
+            };
+            let actual = narrow.solve_position(&Position::<u64>::from(&board), limits);
+            assert_eq!(expected, actual);
+            let actual = wide.solve_position(&Position::<u128>::from(&board), limits);
+            assert_eq!(expected, actual);
+            checked += 1;
+        }
+    }
+
+    fn exhaustive_score(board: &mut ConnectFourBoard) -> i32 {
+        match board.status() {
+            BoardStatus::Winner(_) => return -(44 - board.move_count() as i32) / 2,
+            BoardStatus::Draw => return 0,
+            BoardStatus::OnGoing => {}
+        }
+
+        let mut best = i32::MIN;
+        for column in 1..=7 {
+            let column = Column::try_from(column).unwrap();
+            if board.try_move(column).is_ok() {
+                best = best.max(-exhaustive_score(board));
+                board.pop_move();
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    cmp::Reverse,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use rand::seq::IteratorRandom;
+
+use crate::game::{BoardStatus, ConnectFourBoard, Move};
+
+use super::{
+    AiLevel, EvalWeights, Evaluator, Search, Solver, TranspositionTable, choose_move,
+    evalulate_board,
+    level::{PERFECT_TABLE_SIZE, with_mistakes},
+    ordered_moves, sign_by_player, solve_columns, strongest,
+};
+
+/// Cuts a `Strategy::choose` running on another thread short.
+pub type StopHandle = Arc<dyn Fn() + Send + Sync>;
+
+/// Way of picking moves, so players can be swapped without touching the code that runs
+/// the game.
+pub trait Strategy: Send {
+    fn name(&self) -> String;
+
+    /// Picks a move for the player to move, or `None` when it has no move to offer.
+    fn choose(&mut self, board: &ConnectFourBoard) -> Option<Move>;
+
+    /// Handle that makes the next `choose` return early with the best move found so far,
+    /// even when it is used before that `choose` starts. Handles taken for earlier searches
+    /// no longer affect it. Strategies that can not be interrupted return `None`.
+    fn stop_handle(&mut self) -> Option<StopHandle> {
+        None
+    }
+
+    /// Why the last `choose` had no move to offer, for strategies that can fail to pick one.
+    /// Taking the reason clears it.
+    fn take_error(&mut self) -> Option<String> {
+        None
+    }
+}
+
+/// Plays any legal move.
+#[derive(Debug, Default)]
+pub struct RandomStrategy;
+
+impl Strategy for RandomStrategy {
+    fn name(&self) -> String {
+        "Random".into()
+    }
+
+    fn choose(&mut self, board: &ConnectFourBoard) -> Option<Move> {
+        legal_moves(board).choose(&mut rand::rng())
+    }
+}
+
+/// Plays the move with the best evaluation one move ahead, which takes immediate wins
+/// but never considers the reply.
+#[derive(Debug, Default)]
+pub struct GreedyStrategy;
+
+impl Strategy for GreedyStrategy {
+    fn name(&self) -> String {
+        "Greedy".into()
+    }
+
+    fn choose(&mut self, board: &ConnectFourBoard) -> Option<Move> {
+        let sign = sign_by_player(board.current_player());
+        let moves: Vec<_> = legal_moves(board).collect();
+        let mut board = board.clone();
+        // Ties go to the drop closest to the center.
+        moves.into_iter().min_by_key(|&next_move| {
+            let _ = board.try_play(next_move);
+            let score = sign * evalulate_board(&board, &EvalWeights::DEFAULT);
+            board.pop_move();
+            Reverse(score)
+        })
+    }
+}
+
+/// Searches every move to a fixed depth.
+pub struct MinimaxStrategy {
+    depth: u8,
+    evaluator: Box<dyn Evaluator>,
+    mistake_chance: f64,
+    table: TranspositionTable,
+}
+
+impl MinimaxStrategy {
+    pub fn new(depth: u8) -> Self {
+        Self::with_options(
+            depth,
+            Box::new(EvalWeights::default()),
+            0.0,
+            TranspositionTable::default(),
+        )
+    }
+
+    /// Searches with a custom evaluation, playing a random weaker move instead of
+    /// the best one with a probability of `mistake_chance`.
+    pub fn with_options(
+        depth: u8,
+        evaluator: Box<dyn Evaluator>,
+        mistake_chance: f64,
+        table: TranspositionTable,
+    ) -> Self {
+        Self {
+            depth: depth.max(1),
+            evaluator,
+            mistake_chance,
+            table,
+        }
+    }
+}
+
+impl Strategy for MinimaxStrategy {
+    fn name(&self) -> String {
+        format!("Minimax (depth {})", self.depth)
+    }
+
+    fn choose(&mut self, board: &ConnectFourBoard) -> Option<Move> {
+        if board.status() != BoardStatus::OnGoing {
+            return None;
+        }
+        let mut search = Search::new(&self.table, None, None);
+        search.evaluator = &*self.evaluator;
+        let best_move = search
+            .root(&mut board.clone(), self.depth, None)
+            .map(|(next_move, _)| next_move);
+        with_mistakes(board, best_move, self.mistake_chance)
+    }
+}
+
+/// Plays perfectly by solving every column, which can take minutes early in the game.
+/// The solver follows the standard rules, so PopOut games get no move at all. Its table is
+/// sized for the first position it is asked about and reused for the ones after.
+#[derive(Default)]
+pub struct SolverStrategy {
+    solver: Option<Solver>,
+}
+
+impl Strategy for SolverStrategy {
+    fn name(&self) -> String {
+        "Solver".into()
+    }
+
+    fn choose(&mut self, board: &ConnectFourBoard) -> Option<Move> {
+        if board.status() != BoardStatus::OnGoing {
+            return None;
+        }
+        let solver = self.solver.get_or_insert_with(|| Solver::for_board(board));
+        let scores = solve_columns(solver, &mut board.clone())?;
+        strongest(&scores, board.size()).map(|(column, _)| Move::Drop(column))
+    }
+}
+
+/// Plays at one of the difficulty levels offered in the terminal UI. Only the perfect level
+/// keeps a solver, whose table is reused from move to move.
+pub struct LevelStrategy {
+    level: AiLevel,
+    threads: usize,
+    table: TranspositionTable,
+    solver: Option<Solver>,
+    stop: Arc<AtomicBool>,
+}
+
+impl LevelStrategy {
+    pub fn new(level: AiLevel) -> Self {
+        Self::with_threads(level, 1)
+    }
+
+    /// Searches on `threads` threads rather than one.
+    pub fn with_threads(level: AiLevel, threads: usize) -> Self {
+        Self {
+            level,
+            threads: threads.max(1),
+            table: TranspositionTable::default(),
+            solver: (level == AiLevel::Perfect)
+                .then(|| Solver::new(TranspositionTable::new(PERFECT_TABLE_SIZE))),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Strategy for LevelStrategy {
+    fn name(&self) -> String {
+        self.level.to_string()
+    }
+
+    fn choose(&mut self, board: &ConnectFourBoard) -> Option<Move> {
+        if board.status() != BoardStatus::OnGoing {
+            return None;
+        }
+        choose_move(
+            board,
+            self.level,
+            &mut self.table,
+            self.solver.as_mut(),
+            &self.stop,
+            self.threads,
+        )
+    }
+
+    fn stop_handle(&mut self) -> Option<StopHandle> {
+        self.stop = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&self.stop);
+        Some(Arc::new(move || stop.store(true, Ordering::Relaxed)))
+    }
+}
+
+fn legal_moves(board: &ConnectFourBoard) -> impl Iterator<Item = Move> + '_ {
+    ordered_moves(None, board.size(), board.variant())
+        .filter(|&next_move| board.status() == BoardStatus::OnGoing && board.is_legal(next_move))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{
+        ai::AiLevel,
+        game::{
+            BoardSize, Column, ConnectFourBoard, Move, Rules, Variant,
+            test_positions::double_threat,
+        },
+    };
+
+    use super::{
+        GreedyStrategy, LevelStrategy, MinimaxStrategy, RandomStrategy, SolverStrategy, Strategy,
+    };
+
+    #[test]
+    fn random_plays_legal_moves() {
+        let board = ConnectFourBoard::from_moves("111111").unwrap();
+        let mut strategy = RandomStrategy;
+        for _ in 0..20 {
+            let next_move = strategy.choose(&board).unwrap();
+            assert!(board.is_legal(next_move));
+        }
+    }
+
+    #[test]
+    fn strategies_take_immediate_win() {
+        let board = double_threat();
+        let strategies: [Box<dyn Strategy>; 3] = [
+            Box::new(GreedyStrategy),
+            Box::new(MinimaxStrategy::new(4)),
+            Box::new(SolverStrategy::default()),
+        ];
+        for mut strategy in strategies {
+            assert_eq!(
+                Some(Move::Drop(Column::One)),
+                strategy.choose(&board),
+                "{}",
+                strategy.name()
+            );
+        }
+    }
+
+    #[test]
+    fn no_moves_on_concluded_game() {
+        let board = ConnectFourBoard::from_moves("1212121").unwrap();
+        assert_eq!(None, RandomStrategy.choose(&board));
+        assert_eq!(None, GreedyStrategy.choose(&board));
+        assert_eq!(None, MinimaxStrategy::new(2).choose(&board));
+    }
+
+    #[test]
+    fn solver_passes_on_popout() {
+        let rules = Rules::new(Variant::PopOut, 4).unwrap();
+        let board = ConnectFourBoard::with_rules(BoardSize::STANDARD, rules);
+        assert_eq!(None, SolverStrategy::default().choose(&board));
+    }
+
+    #[test]
+    fn stops_before_search_starts() {
+        let board = ConnectFourBoard::default();
+        let mut strategy = LevelStrategy::new(AiLevel::Hard);
+        strategy.stop_handle().unwrap()();
+
+        let start = Instant::now();
+        assert!(strategy.choose(&board).is_some());
+        assert!(start.elapsed() < AiLevel::Hard.thinking_time());
+    }
+}
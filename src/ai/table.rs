@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::game::{Column, Move};
+
+/// Number of entries allocated by `TranspositionTable::default`.
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+/// Fixed-size table of previously searched positions, indexed by
+/// `ConnectFourBoard::key`. Colliding entries are simply replaced.
+///
+/// Threads searching in parallel share a table without locking it. Every slot holds the
+/// packed entry next to the entry XORed with its key, so a slot torn by two threads
+/// writing at once no longer matches any key and reads as empty.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "transposition table requires at least one entry");
+        Self {
+            slots: (0..size)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, key: u64) -> Option<Entry> {
+        let [check, data] = &self.slots[self.index(key)];
+        let data = data.load(Ordering::Relaxed);
+        let check = check.load(Ordering::Relaxed);
+        if data == 0 || check ^ data != key {
+            return None;
+        }
+        Some(unpack(key, data))
+    }
+
+    pub fn store(&self, entry: Entry) {
+        let [check, data] = &self.slots[self.index(entry.key)];
+        let packed = pack(entry);
+        check.store(entry.key ^ packed, Ordering::Relaxed);
+        data.store(packed, Ordering::Relaxed);
+    }
+
+    // Keys only differ in a few bits per column, so they are scrambled before being
+    // reduced to an index to spread neighbouring positions across the table.
+    fn index(&self, key: u64) -> usize {
+        let hash = key.wrapping_mul(0x9E37_79B9_7F4A_7C15) as u128;
+        ((hash * self.slots.len() as u128) >> 64) as usize
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_SIZE)
+    }
+}
+
+// The score takes the low 32 bits, followed by the depth, the bound and the move. Bounds
+// start at one so a packed entry is never zero, which marks an empty slot.
+fn pack(entry: Entry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    let best_move = match entry.best_move {
+        None => 0,
+        Some(Move::Drop(column)) => column.to_u8() as u64,
+        Some(Move::Pop(column)) => 0x10 | column.to_u8() as u64,
+    };
+    entry.score as u32 as u64 | (entry.depth as u64) << 32 | bound << 40 | best_move << 42
+}
+
+fn unpack(key: u64, data: u64) -> Entry {
+    let bound = match (data >> 40) & 0b11 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    let best_move = Column::try_from((data >> 42) as u8 & 0xF)
+        .ok()
+        .map(|column| match (data >> 46) & 1 {
+            0 => Move::Drop(column),
+            _ => Move::Pop(column),
+        });
+    Entry {
+        key,
+        depth: (data >> 32) as u8,
+        score: data as u32 as i32,
+        bound,
+        best_move,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{Column, Move};
+
+    use super::{Bound, Entry, TranspositionTable};
+
+    #[test]
+    fn stores_and_replaces_entries() {
+        let table = TranspositionTable::new(16);
+        let entries = [
+            Entry {
+                key: 0,
+                depth: 0,
+                score: 0,
+                bound: Bound::Exact,
+                best_move: None,
+            },
+            Entry {
+                key: 42,
+                depth: 12,
+                score: -99_958,
+                bound: Bound::Lower,
+                best_move: Some(Move::Pop(Column::Nine)),
+            },
+            Entry {
+                key: u64::MAX,
+                depth: 255,
+                score: i32::MAX,
+                bound: Bound::Upper,
+                best_move: Some(Move::Drop(Column::One)),
+            },
+        ];
+        for entry in entries {
+            table.store(entry);
+            assert_eq!(Some(entry), table.get(entry.key));
+        }
+
+        let replaced = (0..)
+            .find(|&key| key != 42 && table.index(key) == table.index(42))
+            .unwrap();
+        table.store(Entry {
+            key: replaced,
+            ..entries[1]
+        });
+        assert_eq!(None, table.get(42));
+        assert_eq!(None, TranspositionTable::new(1).get(0));
+    }
+}
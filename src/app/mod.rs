@@ -0,0 +1,555 @@
+use std::{
+    mem,
+    sync::{Arc, atomic::AtomicBool},
+    thread,
+};
+
+use connect_fors::{
+    ai::{AiLevel, LevelStrategy, StopHandle, Strategy},
+    engine::{EngineStrategy, ExternalEngine},
+    game::{BoardSize, BoardStatus, Column, ConnectFourBoard, Move, Player, Rules},
+    net::DEFAULT_PORT,
+};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::{
+    event::{Key, TerminalEvent, TerminalEvents},
+    save::SavedGame,
+    ui,
+};
+
+use network::Network;
+use spectate::Spectate;
+
+mod network;
+mod spectate;
+
+const AI_PLAYER: Player = Player::Two;
+
+// Board sizes offered on the home page, as width and height.
+const BOARD_SIZES: [(u8, u8); 4] = [(7, 6), (8, 7), (9, 7), (6, 5)];
+
+// Lengths of the winning lines offered on the home page.
+const CONNECT_LENGTHS: [u8; 3] = [4, 5, 3];
+
+pub enum CursorMovement {
+    Left,
+    Right,
+    Exact(Column),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Page {
+    Home,
+    SinglePlayer,
+    MultiPlayer,
+    Hosting,
+    Joining,
+    Network,
+    Spectate,
+}
+
+/// Who the player faces on the single-player page.
+#[derive(Clone)]
+pub enum Opponent {
+    Ai,
+    /// Engine in another process, given the current difficulty's thinking time per move.
+    Engine(Arc<ExternalEngine>),
+}
+
+impl Opponent {
+    fn strategy(&self, level: AiLevel, threads: usize) -> Box<dyn Strategy> {
+        match self {
+            Opponent::Ai => Box::new(LevelStrategy::with_threads(level, threads)),
+            Opponent::Engine(engine) => Box::new(EngineStrategy::new(
+                Arc::clone(engine),
+                level.thinking_time(),
+            )),
+        }
+    }
+}
+
+/// Strategy playing the AI's moves, created for the first search. It is handed to the
+/// worker thread for the length of a search and comes back along with the move.
+struct AiPlayer {
+    strategy: Option<Box<dyn Strategy>>,
+    new_strategy: Box<dyn Fn() -> Box<dyn Strategy>>,
+    /// Whether a strategy taken from this player is still out searching. Players replaced
+    /// during a search, such as by picking another level, never lent it.
+    lent: bool,
+}
+
+impl AiPlayer {
+    fn new(new_strategy: impl Fn() -> Box<dyn Strategy> + 'static) -> Self {
+        Self {
+            strategy: None,
+            new_strategy: Box::new(new_strategy),
+            lent: false,
+        }
+    }
+
+    /// Strategy for the next search. A cancelled search may still be holding on to the
+    /// strategy, in which case a fresh one plays instead of waiting for it.
+    fn take(&mut self) -> Box<dyn Strategy> {
+        self.lent = true;
+        self.strategy
+            .take()
+            .unwrap_or_else(|| (self.new_strategy)())
+    }
+
+    /// Keeps a strategy that comes back from a search for the next one, unless it was
+    /// taken from a player this one has since replaced.
+    fn give_back(&mut self, strategy: Box<dyn Strategy>) {
+        if mem::take(&mut self.lent) {
+            self.strategy.get_or_insert(strategy);
+        }
+    }
+}
+
+/// AI search running on a worker thread.
+struct AiTurn {
+    search: u64,
+    stop: Option<StopHandle>,
+}
+
+pub struct App {
+    running: bool,
+    current_page: Page,
+    events: TerminalEvents,
+    board: ConnectFourBoard,
+    board_size: BoardSize,
+    rules: Rules,
+    board_cursor: Option<Column>,
+    redo_stack: Vec<Move>,
+    ai_level: AiLevel,
+    opponent: Opponent,
+    /// Threads the built-in AI searches with.
+    threads: usize,
+    player_names: [String; 2],
+    notice: Option<String>,
+    ai_player: AiPlayer,
+    ai_turn: Option<AiTurn>,
+    searches: u64,
+    join_address: String,
+    stop_hosting: Option<Arc<AtomicBool>>,
+    network: Option<Network>,
+    connections: u64,
+    spectate: Option<Spectate>,
+}
+
+impl App {
+    pub fn new(opponent: Opponent, board_size: BoardSize, rules: Rules, threads: usize) -> Self {
+        let opponent_name = match &opponent {
+            Opponent::Ai => "Player Two".into(),
+            Opponent::Engine(engine) => engine.name().to_string(),
+        };
+        let ai_player = {
+            let opponent = opponent.clone();
+            AiPlayer::new(move || opponent.strategy(AiLevel::default(), threads))
+        };
+        Self {
+            running: true,
+            current_page: Page::Home,
+            events: TerminalEvents::listen(),
+            board: ConnectFourBoard::with_rules(board_size, rules),
+            board_size,
+            rules,
+            board_cursor: None,
+            redo_stack: Vec::new(),
+            ai_level: AiLevel::default(),
+            opponent,
+            threads,
+            player_names: ["Player One".into(), opponent_name],
+            notice: None,
+            ai_player,
+            ai_turn: None,
+            searches: 0,
+            join_address: format!("127.0.0.1:{}", DEFAULT_PORT),
+            stop_hosting: None,
+            network: None,
+            connections: 0,
+            spectate: None,
+        }
+    }
+
+    pub fn run(&mut self, mut terminal: DefaultTerminal) -> anyhow::Result<()> {
+        while self.running {
+            terminal.draw(|frame| self.draw(frame))?;
+            match self.events.next()? {
+                TerminalEvent::Input(key) => self.handle_key_press(key),
+                TerminalEvent::AiMove {
+                    search,
+                    ai_move,
+                    strategy,
+                } => self.handle_ai_move(search, ai_move, strategy),
+                TerminalEvent::Connected(connection) => self.handle_connected(connection),
+                TerminalEvent::Peer {
+                    connection,
+                    message,
+                } => self.handle_peer_message(connection, message),
+                TerminalEvent::Tick => self.handle_spectate_tick(),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_key_press(&mut self, key: Key) {
+        self.notice = None;
+        match self.current_page {
+            Page::Home => self.handle_home_key_press(key),
+            Page::SinglePlayer => self.handle_singleplayer_key_press(key),
+            Page::MultiPlayer => self.handle_multiplayer_key_press(key),
+            Page::Hosting => self.handle_hosting_key_press(key),
+            Page::Joining => self.handle_joining_key_press(key),
+            Page::Network => self.handle_network_key_press(key),
+            Page::Spectate => self.handle_spectate_key_press(key),
+        }
+    }
+
+    fn handle_home_key_press(&mut self, key: Key) {
+        match key {
+            Key::Char('1') => self.start_local_game(Page::SinglePlayer),
+            Key::Char('2') => self.start_local_game(Page::MultiPlayer),
+            Key::Char('3') => self.start_spectating(),
+            Key::Char('h') => self.host_game(),
+            Key::Char('j') => self.current_page = Page::Joining,
+            Key::Char('l') => self.load_game(),
+            Key::Char('d') => self.set_ai_level(self.ai_level.next()),
+            Key::Char('s') => self.board_size = next_board_size(self.board_size),
+            Key::Char('v') => self.rules = next_variant(self.rules),
+            Key::Char('c') => self.rules = next_connect(self.rules),
+            Key::Char('q') => self.running = false,
+            _ => {}
+        }
+    }
+
+    fn start_local_game(&mut self, page: Page) {
+        self.board = ConnectFourBoard::with_rules(self.board_size, self.rules);
+        self.board_cursor = Some(self.board_size.center());
+        self.redo_stack.clear();
+        self.current_page = page;
+    }
+
+    fn handle_game_key_press<F>(&mut self, key: Key, mut on_turn: F)
+    where
+        F: FnMut(&mut Self, Move),
+    {
+        match key {
+            Key::Char('q') => {
+                self.current_page = Page::Home;
+            }
+            Key::Ctrl('s') => self.save_game(),
+            Key::Char('u') => self.undo(),
+            Key::Char('r') => self.redo(),
+            Key::Left => self.update_cursor(CursorMovement::Left),
+            Key::Right => self.update_cursor(CursorMovement::Right),
+            Key::Char(digit @ '1'..='9') => {
+                if let Ok(column) = Column::try_from(digit as u8 - b'0') {
+                    self.update_cursor(CursorMovement::Exact(column));
+                }
+            }
+            Key::Enter if self.board().status() == BoardStatus::OnGoing => {
+                on_turn(self, Move::Drop(self.board_cursor.unwrap()))
+            }
+            Key::Char('p') if self.board().status() == BoardStatus::OnGoing => {
+                on_turn(self, Move::Pop(self.board_cursor.unwrap()))
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_singleplayer_key_press(&mut self, key: Key) {
+        if self.ai_turn.is_some() {
+            match key {
+                Key::Char('c') => {
+                    // Takes back the move the AI was responding to, which can be redone to
+                    // have the AI reply to it after all.
+                    self.cancel_ai_turn();
+                    if let Some(last_move) = self.board.pop_move() {
+                        self.redo_stack.push(last_move);
+                    }
+                }
+                Key::Char('q') => {
+                    self.cancel_ai_turn();
+                    self.current_page = Page::Home;
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        self.handle_game_key_press(key, |app, next_move| {
+            if app.board.try_play(next_move).is_ok() {
+                app.redo_stack.clear();
+                if app.board.status() == BoardStatus::OnGoing {
+                    app.start_ai_turn();
+                }
+            }
+        });
+    }
+
+    fn set_ai_level(&mut self, level: AiLevel) {
+        self.ai_level = level;
+        let (opponent, threads) = (self.opponent.clone(), self.threads);
+        self.ai_player = AiPlayer::new(move || opponent.strategy(level, threads));
+    }
+
+    /// Searches for the move of the player to move, which is the opponent on the
+    /// single-player page and either AI when spectating.
+    fn start_ai_turn(&mut self) {
+        let mut strategy = self.ai_player_to_move().take();
+        self.searches += 1;
+        let search = self.searches;
+        // The handle is taken before the search starts, so cancelling right away still
+        // stops it.
+        self.ai_turn = Some(AiTurn {
+            search,
+            stop: strategy.stop_handle(),
+        });
+
+        let board = self.board.clone();
+        let tx = self.events.sender();
+        thread::spawn(move || {
+            let ai_move = strategy.choose(&board);
+            let _ = tx.send(TerminalEvent::AiMove {
+                search,
+                ai_move,
+                strategy,
+            });
+        });
+    }
+
+    fn ai_player_to_move(&mut self) -> &mut AiPlayer {
+        match &mut self.spectate {
+            Some(spectate) if self.current_page == Page::Spectate => {
+                spectate.player_mut(self.board.current_player())
+            }
+            _ => &mut self.ai_player,
+        }
+    }
+
+    fn cancel_ai_turn(&mut self) {
+        if let Some(AiTurn {
+            stop: Some(stop), ..
+        }) = self.ai_turn.take()
+        {
+            stop();
+        }
+    }
+
+    fn handle_ai_move(
+        &mut self,
+        search: u64,
+        ai_move: Option<Move>,
+        mut strategy: Box<dyn Strategy>,
+    ) {
+        // Results of cancelled searches are dropped along with their strategies.
+        if self
+            .ai_turn
+            .as_ref()
+            .is_none_or(|turn| turn.search != search)
+        {
+            return;
+        }
+        self.ai_turn = None;
+        let error = strategy.take_error();
+        self.ai_player_to_move().give_back(strategy);
+
+        match ai_move {
+            Some(ai_move) => {
+                let _ = self.board.try_play(ai_move);
+            }
+            None if self.current_page == Page::Spectate => self.pause_spectating(),
+            None => {
+                // Takes back the move the AI was responding to, so the player can try again.
+                self.board.pop_move();
+                let notice = "The opponent did not reply with a move.";
+                self.notice = Some(match error {
+                    Some(error) => format!("{notice} {error}"),
+                    None => notice.into(),
+                });
+            }
+        }
+    }
+
+    fn handle_multiplayer_key_press(&mut self, key: Key) {
+        self.handle_game_key_press(key, |app, next_move| {
+            if app.board.try_play(next_move).is_ok() {
+                app.redo_stack.clear();
+            }
+        });
+    }
+
+    /// Takes back the last move. Against the AI, its reply is taken back along with the
+    /// player's move so that it is the player's turn again.
+    fn undo(&mut self) {
+        while let Some(last_move) = self.board.pop_move() {
+            self.redo_stack.push(last_move);
+            if !self.is_ai_to_move() {
+                break;
+            }
+        }
+    }
+
+    fn redo(&mut self) {
+        while let Some(next_move) = self.redo_stack.pop() {
+            let _ = self.board.try_play(next_move);
+            if !self.is_ai_to_move() {
+                break;
+            }
+        }
+        // A move whose reply was cancelled comes back without one, so the AI replies now.
+        if self.is_ai_to_move() && self.board.status() == BoardStatus::OnGoing {
+            self.start_ai_turn();
+        }
+    }
+
+    fn save_game(&mut self) {
+        let game = SavedGame {
+            page: self.current_page,
+            ai_level: self.ai_level,
+            player_names: self.player_names.clone(),
+            board: self.board.clone(),
+        };
+        self.notice = Some(match game.write() {
+            Ok(path) => format!("Game saved to {}", path.display()),
+            Err(err) => format!("Could not save game: {}", err),
+        });
+    }
+
+    fn load_game(&mut self) {
+        let game = match SavedGame::read() {
+            Ok(game) => game,
+            Err(err) => {
+                self.notice = Some(format!("Could not load game: {}", err));
+                return;
+            }
+        };
+
+        self.current_page = game.page;
+        self.set_ai_level(game.ai_level);
+        self.player_names = game.player_names;
+        // New games keep the size and rules of the loaded one, as if it was started here.
+        self.board_size = game.board.size();
+        self.rules = game.board.rules();
+        self.board_cursor = Some(self.board_size.center());
+        self.board = game.board;
+        self.redo_stack.clear();
+        // The game may have been saved while the AI was thinking.
+        if self.is_ai_to_move() && self.board.status() == BoardStatus::OnGoing {
+            self.start_ai_turn();
+        }
+    }
+
+    fn is_ai_to_move(&self) -> bool {
+        matches!(self.current_page, Page::SinglePlayer) && self.board.current_player() == AI_PLAYER
+    }
+
+    fn update_cursor(&mut self, cursor: CursorMovement) {
+        match cursor {
+            CursorMovement::Left if self.board_cursor.is_some() => {
+                let col = self.board_cursor.unwrap().to_u8();
+                if let Ok(column) = Column::try_from(col - 1) {
+                    self.board_cursor = Some(column);
+                }
+            }
+            CursorMovement::Right if self.board_cursor.is_some() => {
+                let col = self.board_cursor.unwrap().to_u8();
+                if let Ok(column) = Column::try_from(col + 1)
+                    && self.is_selectable(column)
+                {
+                    self.board_cursor = Some(column);
+                }
+            }
+            CursorMovement::Exact(column) if self.is_selectable(column) => {
+                self.board_cursor = Some(column);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the cursor can rest on `column`, which PopOut also allows for full columns
+    /// with a disc of the player to move at the bottom.
+    fn is_selectable(&self, column: Column) -> bool {
+        self.board.is_playable(column) || self.board.can_pop(column)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        match self.current_page {
+            Page::Home => ui::draw_home(frame, self),
+            Page::SinglePlayer | Page::MultiPlayer | Page::Network | Page::Spectate => {
+                ui::draw_game(frame, self)
+            }
+            Page::Hosting | Page::Joining => ui::draw_lobby(frame, self),
+        }
+    }
+
+    pub fn board(&self) -> &ConnectFourBoard {
+        &self.board
+    }
+
+    pub fn board_cursor(&self) -> Option<Column> {
+        self.board_cursor
+    }
+
+    pub fn ai_level(&self) -> AiLevel {
+        self.ai_level
+    }
+
+    pub fn player_names(&self) -> &[String; 2] {
+        &self.player_names
+    }
+
+    pub fn notice(&self) -> Option<&str> {
+        self.notice.as_deref()
+    }
+
+    pub fn join_address(&self) -> &str {
+        &self.join_address
+    }
+
+    pub fn current_page(&self) -> Page {
+        self.current_page
+    }
+
+    pub fn is_ai_thinking(&self) -> bool {
+        self.ai_turn.is_some()
+    }
+
+    /// Size of the boards new local games are played on.
+    pub fn board_size(&self) -> BoardSize {
+        self.board_size
+    }
+
+    /// Rules new local games are played by.
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+}
+
+/// The size following `size` in the home page's list, wrapping back around to the first.
+fn next_board_size(size: BoardSize) -> BoardSize {
+    let sizes = BOARD_SIZES.map(|(width, height)| {
+        BoardSize::new(width, height).expect("offered board sizes are supported")
+    });
+    let next = sizes
+        .iter()
+        .position(|&offered| offered == size)
+        .map_or(0, |idx| (idx + 1) % sizes.len());
+    sizes[next]
+}
+
+/// `rules` with the variant following theirs, keeping the line length.
+fn next_variant(rules: Rules) -> Rules {
+    Rules::new(rules.variant().next(), rules.connect()).expect("line length is unchanged")
+}
+
+/// `rules` with the line length following theirs in the home page's list, wrapping back
+/// around to the first.
+fn next_connect(rules: Rules) -> Rules {
+    let next = CONNECT_LENGTHS
+        .iter()
+        .position(|&offered| offered == rules.connect())
+        .map_or(0, |idx| (idx + 1) % CONNECT_LENGTHS.len());
+    Rules::new(rules.variant(), CONNECT_LENGTHS[next]).expect("offered lengths are supported")
+}
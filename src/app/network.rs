@@ -0,0 +1,254 @@
+use std::{
+    net::TcpListener,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+use connect_fors::{
+    game::Player,
+    net::{Connection, DEFAULT_PORT, Message, NetError, NetworkGame, RemoteEvent},
+};
+
+use crate::event::{Key, TerminalEvent};
+
+use super::{App, Page};
+
+/// Game against another instance over TCP.
+pub struct Network {
+    pub game: NetworkGame,
+    connection: Connection,
+    id: u64,
+}
+
+impl App {
+    pub(super) fn host_game(&mut self) {
+        let listener = match TcpListener::bind(("0.0.0.0", DEFAULT_PORT)) {
+            Ok(listener) => listener,
+            Err(err) => {
+                self.notice = Some(format!("Could not host game: {}", err));
+                return;
+            }
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        self.stop_hosting = Some(Arc::clone(&stop));
+        self.current_page = Page::Hosting;
+
+        let tx = self.events.sender();
+        thread::spawn(move || {
+            let connection = Connection::accept(&listener, &stop);
+            let _ = tx.send(TerminalEvent::Connected(connection));
+        });
+    }
+
+    pub(super) fn handle_hosting_key_press(&mut self, key: Key) {
+        if let Key::Char('q') | Key::Escape = key {
+            if let Some(stop) = self.stop_hosting.take() {
+                stop.store(true, Ordering::Relaxed);
+            }
+            self.current_page = Page::Home;
+        }
+    }
+
+    pub(super) fn handle_joining_key_press(&mut self, key: Key) {
+        match key {
+            Key::Escape => self.current_page = Page::Home,
+            Key::Backspace => {
+                self.join_address.pop();
+            }
+            Key::Char(c) => self.join_address.push(c),
+            Key::Enter => {
+                self.notice = Some(format!("Connecting to {}...", self.join_address));
+                let address = self.join_address.clone();
+                let tx = self.events.sender();
+                thread::spawn(move || {
+                    let connection = Connection::join(address.as_str());
+                    let _ = tx.send(TerminalEvent::Connected(connection));
+                });
+            }
+            _ => {}
+        }
+    }
+
+    pub(super) fn handle_connected(&mut self, connection: Result<Connection, NetError>) {
+        // The host moves first in the first game.
+        let local_player = match self.current_page {
+            Page::Hosting => Player::One,
+            Page::Joining => Player::Two,
+            _ => {
+                // The lobby was left before the connection was made.
+                if let Ok(connection) = connection {
+                    connection.shutdown();
+                }
+                return;
+            }
+        };
+        self.stop_hosting = None;
+
+        let connection = match connection.and_then(|connection| {
+            let reader = connection.try_clone()?;
+            Ok((connection, reader))
+        }) {
+            Ok(connection) => connection,
+            Err(err) => {
+                self.notice = Some(format!("Could not connect: {}", err));
+                return;
+            }
+        };
+
+        let (connection, mut reader) = connection;
+        self.connections += 1;
+        let id = self.connections;
+        let tx = self.events.sender();
+        thread::spawn(move || {
+            loop {
+                let message = reader.receive();
+                let closed = matches!(message, Err(_) | Ok(Message::Bye));
+                if tx
+                    .send(TerminalEvent::Peer {
+                        connection: id,
+                        message,
+                    })
+                    .is_err()
+                    || closed
+                {
+                    break;
+                }
+            }
+        });
+
+        // Both sides start with their own choice, until the host's arrives at the guest.
+        let game = NetworkGame::new(local_player, self.board_size, self.rules);
+        let rules = game.rules_message();
+        self.board = game.board().clone();
+        self.network = Some(Network {
+            game,
+            connection,
+            id,
+        });
+        self.board_cursor = Some(self.board.size().center());
+        self.current_page = Page::Network;
+        if local_player == Player::One {
+            self.send(rules);
+        }
+    }
+
+    pub(super) fn handle_network_key_press(&mut self, key: Key) {
+        match key {
+            Key::Char('q') => {
+                self.leave_network_game(Some(Message::Bye));
+                self.current_page = Page::Home;
+            }
+            Key::Char('m') => {
+                if let Some(network) = &mut self.network {
+                    match network.game.request_rematch() {
+                        Ok(message) => {
+                            self.notice =
+                                Some("Waiting for the opponent to accept the rematch.".into());
+                            self.send(message);
+                        }
+                        Err(err) => self.notice = Some(err.to_string()),
+                    }
+                }
+            }
+            // Network games can not be saved or taken back.
+            Key::Char('u' | 'r') | Key::Ctrl('s') => {}
+            key => self.handle_game_key_press(key, |app, next_move| {
+                let Some(network) = &mut app.network else {
+                    return;
+                };
+                match network.game.play(next_move) {
+                    Ok(message) => app.send(message),
+                    Err(err) => app.notice = Some(err.to_string()),
+                }
+            }),
+        }
+    }
+
+    pub(super) fn handle_peer_message(
+        &mut self,
+        connection: u64,
+        message: Result<Message, NetError>,
+    ) {
+        let Some(network) = self
+            .network
+            .as_mut()
+            .filter(|network| network.id == connection)
+        else {
+            return;
+        };
+
+        let event = match message.and_then(|message| network.game.handle(message)) {
+            Ok(event) => event,
+            // Rejected moves are named, so the peer knows which one to take back.
+            Err(err @ NetError::IllegalMove(next_move)) => {
+                self.send(Message::Reject(next_move, err.to_string()));
+                return;
+            }
+            Err(
+                err @ (NetError::InvalidMessage(_) | NetError::GameNotOver | NetError::GameStarted),
+            ) => {
+                self.send(Message::Error(err.to_string()));
+                return;
+            }
+            Err(err) => {
+                self.notice = Some(format!("Connection lost: {}", err));
+                self.leave_network_game(None);
+                return;
+            }
+        };
+
+        self.notice = match event {
+            RemoteEvent::RulesSet => {
+                self.board_cursor = Some(network.game.board().size().center());
+                None
+            }
+            RemoteEvent::Moved(_) => None,
+            RemoteEvent::RematchRequested => {
+                Some("The opponent wants a rematch, press (m) to accept.".into())
+            }
+            RemoteEvent::RematchStarted => Some("Rematch started, colors have swapped.".into()),
+            RemoteEvent::Rejected(reason) => Some(format!("The opponent rejected: {}", reason)),
+            RemoteEvent::Disconnected => {
+                self.leave_network_game(None);
+                Some("The opponent left the game.".into())
+            }
+        };
+        self.sync_network_board();
+    }
+
+    fn send(&mut self, message: Message) {
+        let Some(network) = &mut self.network else {
+            return;
+        };
+        if let Err(err) = network.connection.send(&message) {
+            self.notice = Some(format!("Connection lost: {}", err));
+            self.leave_network_game(None);
+            return;
+        }
+        self.sync_network_board();
+    }
+
+    /// Closes the connection while leaving the final board on screen.
+    fn leave_network_game(&mut self, farewell: Option<Message>) {
+        if let Some(mut network) = self.network.take() {
+            if let Some(message) = farewell {
+                let _ = network.connection.send(&message);
+            }
+            network.connection.shutdown();
+        }
+    }
+
+    fn sync_network_board(&mut self) {
+        if let Some(network) = &self.network {
+            self.board = network.game.board().clone();
+        }
+    }
+
+    pub fn network_game(&self) -> Option<&NetworkGame> {
+        self.network.as_ref().map(|network| &network.game)
+    }
+}
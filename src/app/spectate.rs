@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use connect_fors::{
+    ai::{AiLevel, LevelStrategy},
+    game::{BoardStatus, ConnectFourBoard, Player},
+};
+
+use crate::event::Key;
+
+use super::{AiPlayer, App, Page};
+
+// Moves are paced by the terminal's tick events, which arrive every 250ms.
+const TICK_RATE: Duration = Duration::from_millis(250);
+const MAX_DELAY: u32 = 16;
+
+/// Game between two AI players, watched on the game screen.
+pub struct Spectate {
+    levels: [AiLevel; 2],
+    threads: usize,
+    players: [AiPlayer; 2],
+    paused: bool,
+    /// Ticks to wait after a move before the next search starts.
+    delay: u32,
+    ticks: u32,
+}
+
+impl Spectate {
+    fn new(levels: [AiLevel; 2], threads: usize) -> Self {
+        Self {
+            levels,
+            threads,
+            players: levels.map(|level| level_player(level, threads)),
+            paused: false,
+            delay: 4,
+            ticks: 0,
+        }
+    }
+
+    pub(super) fn player_mut(&mut self, player: Player) -> &mut AiPlayer {
+        &mut self.players[index(player)]
+    }
+
+    pub fn level(&self, player: Player) -> AiLevel {
+        self.levels[index(player)]
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Time between one move being played and the search for the next one starting.
+    pub fn delay(&self) -> Duration {
+        TICK_RATE * self.delay
+    }
+
+    fn set_level(&mut self, player: Player, level: AiLevel) {
+        self.levels[index(player)] = level;
+        self.players[index(player)] = level_player(level, self.threads);
+    }
+}
+
+impl App {
+    pub(super) fn start_spectating(&mut self) {
+        // Both sides start at the single-player difficulty and keep their levels between games.
+        let levels = self
+            .spectate
+            .as_ref()
+            .map_or([self.ai_level; 2], |spectate| spectate.levels);
+        self.spectate = Some(Spectate::new(levels, self.threads));
+        self.board = ConnectFourBoard::with_rules(self.board_size, self.rules);
+        self.board_cursor = None;
+        self.redo_stack.clear();
+        self.current_page = Page::Spectate;
+    }
+
+    pub(super) fn pause_spectating(&mut self) {
+        if let Some(spectate) = &mut self.spectate {
+            spectate.paused = true;
+        }
+        self.notice = Some("The AI did not find a move.".into());
+    }
+
+    pub(super) fn handle_spectate_key_press(&mut self, key: Key) {
+        let Some(spectate) = &mut self.spectate else {
+            return;
+        };
+        match key {
+            Key::Char(' ') => {
+                spectate.paused = !spectate.paused;
+                spectate.ticks = 0;
+            }
+            Key::Char('s')
+                if spectate.paused
+                    && self.ai_turn.is_none()
+                    && self.board.status() == BoardStatus::OnGoing =>
+            {
+                self.start_ai_turn();
+            }
+            Key::Char('+' | '=') => spectate.delay = spectate.delay.saturating_sub(1).max(1),
+            Key::Char('-') => spectate.delay = (spectate.delay + 1).min(MAX_DELAY),
+            Key::Char('a') => spectate.set_level(Player::One, spectate.level(Player::One).next()),
+            Key::Char('b') => spectate.set_level(Player::Two, spectate.level(Player::Two).next()),
+            Key::Char('n') => {
+                self.cancel_ai_turn();
+                self.start_spectating();
+            }
+            Key::Char('q') => {
+                self.cancel_ai_turn();
+                self.current_page = Page::Home;
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts the next search once the delay since the last move has passed.
+    pub(super) fn handle_spectate_tick(&mut self) {
+        let Some(spectate) = &mut self.spectate else {
+            return;
+        };
+        if self.current_page != Page::Spectate
+            || spectate.paused
+            || self.ai_turn.is_some()
+            || self.board.status() != BoardStatus::OnGoing
+        {
+            return;
+        }
+
+        spectate.ticks += 1;
+        if spectate.ticks >= spectate.delay {
+            spectate.ticks = 0;
+            self.start_ai_turn();
+        }
+    }
+
+    pub fn spectate(&self) -> Option<&Spectate> {
+        self.spectate.as_ref()
+    }
+}
+
+fn level_player(level: AiLevel, threads: usize) -> AiPlayer {
+    AiPlayer::new(move || Box::new(LevelStrategy::with_threads(level, threads)))
+}
+
+fn index(player: Player) -> usize {
+    match player {
+        Player::One => 0,
+        Player::Two => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use connect_fors::{ai::AiLevel, game::Player};
+
+    use super::Spectate;
+
+    #[test]
+    fn plays_new_level_after_search_returns() {
+        let mut spectate = Spectate::new([AiLevel::Easy; 2], 1);
+        let strategy = spectate.player_mut(Player::One).take();
+        // The level changes while the search is running.
+        spectate.set_level(Player::One, AiLevel::Hard);
+        spectate.player_mut(Player::One).give_back(strategy);
+        assert_eq!(
+            AiLevel::Hard.to_string(),
+            spectate.player_mut(Player::One).take().name()
+        );
+    }
+}
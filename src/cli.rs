@@ -0,0 +1,969 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    time::Instant,
+};
+
+use connect_fors::{
+    ai::{self, BenchReport, Book, Evaluation, Score, SearchLimits, Solver},
+    engine,
+    game::{BoardSize, Column, ConnectFourBoard, ParseError, Rules},
+    tournament::{self, PlayerConfig, TournamentError, TournamentOptions, TournamentReport},
+};
+use thiserror::Error;
+
+use crate::save::data_path;
+
+const DEFAULT_DEPTH: u8 = 12;
+const DEFAULT_BOOK_PLIES: u8 = 6;
+const BOOK_FILE: &str = "book.bin";
+
+pub const USAGE: &str = "\
+Usage: connect-fors [command] [options]
+
+Commands:
+  play                 Play in the terminal UI (default)
+  best-move <moves>    Print the strongest column for the player to move
+  solve <moves>        Solve the position exactly
+  analyze <moves>      Score every playable column
+  bench                Search a fixed set of positions and report the speed
+  engine               Speak the engine protocol over stdin and stdout
+  tournament <players> Play AI players against each other and rate them
+  build-book           Solve every opening position up to a number of plies into a book
+  book <moves>         Print the book's rules and its scores for a position
+  help                 Print this message
+
+Moves are column digits played in order, such as 4453. Leave them out for an empty board.
+Tournament players are comma separated settings, such as depth=6,threats=4,random=0.1,
+where position and threats weigh the table evaluation, eval=threats switches to the
+threat-aware evaluation and random is the chance of a mistake.
+
+Options:
+  --depth <plies>      Search to a fixed depth (default 12)
+  --time <ms>          Search for a fixed amount of time
+  --perfect            Solve every column exactly, which can take minutes early on
+  --eval <name>        Evaluation searched by bench, table (default) or threats
+  --size <width>x<height>
+                       Board size between 4x4 and 9x8 (default 7x6)
+  --variant <name>     Rules to play and hold tournaments by, standard (default) or popout
+  --connect <length>   Discs in a row that win, between 3 and 5 (default 4)
+  --json               Print the result as JSON
+  --engine <command>   Play against an external engine process instead of the built-in AI
+  --rounds <count>     Openings each pair of players plays with both colors (default 10)
+  --openings <moves>   Random moves that start every tournament game (default 2)
+  --threads <count>    Threads the AI searches with (default 1), or tournament games
+                       played at once (default one per CPU core)
+  --plies <count>      Moves into the game the book covers (default 6)
+  --book <path>        Opening book the AI plays from (default book.bin in the data directory)
+";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CliError {
+    #[error("Unknown command '{0}'.")]
+    UnknownCommand(String),
+
+    #[error("Option '{0}' is not supported by this command.")]
+    UnknownOption(String),
+
+    #[error("Option '{0}' expects a value.")]
+    MissingValue(&'static str),
+
+    #[error("Option '{0}' has an invalid value '{1}'.")]
+    InvalidValue(&'static str, String),
+
+    #[error("Unexpected argument '{0}'.")]
+    UnexpectedArgument(String),
+
+    #[error("Invalid move sequence. {0}")]
+    InvalidMoves(#[from] ParseError),
+
+    #[error("Invalid tournament player. {0}")]
+    InvalidPlayer(#[from] TournamentError),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Play {
+        /// Command line of an external engine to play against.
+        engine: Option<String>,
+        /// Size of the boards new games start with.
+        size: BoardSize,
+        /// Rules new games are played by.
+        rules: Rules,
+        /// Threads the built-in AI searches with.
+        threads: usize,
+    },
+    Help,
+    BestMove {
+        board: ConnectFourBoard,
+        limits: SearchLimits,
+        threads: usize,
+    },
+    Solve {
+        board: ConnectFourBoard,
+    },
+    Analyze {
+        board: ConnectFourBoard,
+        limits: SearchLimits,
+        threads: usize,
+    },
+    Bench {
+        depth: u8,
+        evaluation: Evaluation,
+        threads: usize,
+    },
+    Engine {
+        threads: usize,
+    },
+    Tournament {
+        players: Vec<PlayerConfig>,
+        options: TournamentOptions,
+    },
+    BuildBook {
+        size: BoardSize,
+        rules: Rules,
+        plies: u8,
+    },
+    Book {
+        /// Position to look up, played on the book's board.
+        moves: String,
+    },
+}
+
+/// Parsed command line, run without the terminal UI unless the command is `play`.
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub format: Format,
+    /// Opening book given on the command line.
+    pub book: Option<PathBuf>,
+}
+
+/// What a command accepts on the command line besides `--json`, which every command does.
+struct CommandSpec {
+    name: &'static str,
+    options: &'static [&'static str],
+    /// Most arguments that are not options, such as the moves of the analysis commands.
+    arguments: usize,
+}
+
+// The analysis commands solve and score drops by the standard rules, and books bring
+// their own board and rules.
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "play",
+        options: &[
+            "--engine",
+            "--size",
+            "--variant",
+            "--connect",
+            "--threads",
+            "--book",
+        ],
+        arguments: 0,
+    },
+    CommandSpec {
+        name: "help",
+        options: &[],
+        arguments: 0,
+    },
+    CommandSpec {
+        name: "best-move",
+        options: &[
+            "--depth",
+            "--time",
+            "--perfect",
+            "--size",
+            "--connect",
+            "--threads",
+        ],
+        arguments: 1,
+    },
+    CommandSpec {
+        name: "solve",
+        options: &["--size", "--connect"],
+        arguments: 1,
+    },
+    CommandSpec {
+        name: "analyze",
+        options: &[
+            "--depth",
+            "--time",
+            "--perfect",
+            "--size",
+            "--connect",
+            "--threads",
+        ],
+        arguments: 1,
+    },
+    CommandSpec {
+        name: "bench",
+        options: &["--depth", "--eval", "--threads"],
+        arguments: 0,
+    },
+    CommandSpec {
+        name: "engine",
+        options: &["--threads"],
+        arguments: 0,
+    },
+    CommandSpec {
+        name: "tournament",
+        options: &[
+            "--size",
+            "--variant",
+            "--connect",
+            "--threads",
+            "--rounds",
+            "--openings",
+        ],
+        arguments: usize::MAX,
+    },
+    CommandSpec {
+        name: "build-book",
+        options: &["--size", "--connect", "--plies", "--book"],
+        arguments: 0,
+    },
+    CommandSpec {
+        name: "book",
+        options: &["--book"],
+        arguments: 1,
+    },
+];
+
+impl Cli {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut args = args.into_iter();
+        let name = match args.next() {
+            None => "play".to_string(),
+            Some(name) if name == "--help" || name == "-h" => "help".to_string(),
+            Some(name) => name,
+        };
+        let spec = COMMANDS
+            .iter()
+            .find(|spec| spec.name == name)
+            .ok_or(CliError::UnknownCommand(name))?;
+
+        let mut arguments = Vec::new();
+        let mut limits = None;
+        let mut engine = None;
+        let mut evaluation = None;
+        let mut size = None;
+        let mut variant = None;
+        let mut connect = None;
+        let mut plies = None;
+        let mut threads = None;
+        let mut book = None;
+        let mut options = TournamentOptions::default();
+        let mut format = Format::Text;
+        while let Some(arg) = args.next() {
+            if arg.starts_with("--") && arg != "--json" && !spec.options.contains(&arg.as_str()) {
+                return Err(CliError::UnknownOption(arg));
+            }
+            match arg.as_str() {
+                "--depth" => {
+                    let depth = option_value("--depth", args.next())?;
+                    limits = Some(SearchLimits::Depth(depth));
+                }
+                "--time" => {
+                    let millis = option_value("--time", args.next())?;
+                    limits = Some(SearchLimits::Time(std::time::Duration::from_millis(millis)));
+                }
+                "--perfect" => limits = Some(SearchLimits::Perfect),
+                "--json" => format = Format::Json,
+                "--engine" => engine = Some(option_value("--engine", args.next())?),
+                "--eval" => evaluation = Some(option_value("--eval", args.next())?),
+                "--size" => size = Some(option_value("--size", args.next())?),
+                "--variant" => variant = Some(option_value("--variant", args.next())?),
+                "--connect" => connect = Some(option_value("--connect", args.next())?),
+                "--plies" => plies = Some(option_value("--plies", args.next())?),
+                "--book" => book = Some(option_value("--book", args.next())?),
+                "--rounds" => options.rounds = option_value("--rounds", args.next())?,
+                "--openings" => options.opening_moves = option_value("--openings", args.next())?,
+                "--threads" => threads = Some(option_value("--threads", args.next())?),
+                _ if arguments.len() == spec.arguments => {
+                    return Err(CliError::UnexpectedArgument(arg));
+                }
+                _ => arguments.push(arg),
+            }
+        }
+
+        let size = size.unwrap_or_default();
+        let rules = Rules::new(
+            variant.unwrap_or_default(),
+            connect.unwrap_or(Rules::STANDARD.connect()),
+        )
+        .map_err(|error| CliError::InvalidValue("--connect", error.0.to_string()))?;
+        let moves = arguments.first().cloned().unwrap_or_default();
+        let board = || {
+            let mut board = ConnectFourBoard::with_rules(size, rules);
+            board.play_moves(&moves)?;
+            Ok::<_, ParseError>(board)
+        };
+        let command = match spec.name {
+            "play" => Command::Play {
+                engine,
+                size,
+                rules,
+                threads: threads.unwrap_or(1),
+            },
+            "help" => Command::Help,
+            "best-move" => Command::BestMove {
+                board: board()?,
+                limits: limits.unwrap_or(SearchLimits::Depth(DEFAULT_DEPTH)),
+                threads: threads.unwrap_or(1),
+            },
+            "solve" => Command::Solve { board: board()? },
+            "analyze" => Command::Analyze {
+                board: board()?,
+                limits: limits.unwrap_or(SearchLimits::Depth(DEFAULT_DEPTH)),
+                threads: threads.unwrap_or(1),
+            },
+            "bench" => Command::Bench {
+                depth: match limits {
+                    Some(SearchLimits::Depth(depth)) => depth,
+                    _ => DEFAULT_DEPTH,
+                },
+                evaluation: evaluation.unwrap_or_default(),
+                threads: threads.unwrap_or(1),
+            },
+            "engine" => Command::Engine {
+                threads: threads.unwrap_or(1),
+            },
+            "tournament" => Command::Tournament {
+                players: arguments
+                    .iter()
+                    .map(|player| player.parse())
+                    .collect::<Result<_, _>>()?,
+                options: TournamentOptions {
+                    size,
+                    rules,
+                    threads: threads.unwrap_or(options.threads),
+                    ..options
+                },
+            },
+            "build-book" => Command::BuildBook {
+                size,
+                rules,
+                plies: plies.unwrap_or(DEFAULT_BOOK_PLIES),
+            },
+            "book" => Command::Book { moves },
+            _ => unreachable!("every command in the table is parsed"),
+        };
+
+        Ok(Self {
+            command,
+            format,
+            book,
+        })
+    }
+
+    /// Opening book given with `--book`, or the one `build-book` writes by default.
+    pub fn book_path(&self) -> anyhow::Result<PathBuf> {
+        match &self.book {
+            Some(path) => Ok(path.clone()),
+            None => data_path(BOOK_FILE),
+        }
+    }
+
+    /// Runs a headless command, writing its result to `out`.
+    pub fn run(self, out: &mut (impl Write + Send)) -> anyhow::Result<()> {
+        let json = self.format == Format::Json;
+        let book_path = self.book_path();
+        match self.command {
+            Command::Play { .. } => anyhow::bail!("The play command needs a terminal."),
+            Command::Help => write!(out, "{}", USAGE)?,
+            Command::Engine { threads } => engine::run(io::stdin().lock(), out, threads)?,
+            Command::Tournament { players, options } => {
+                let report = tournament::run(&players, options)?;
+                if json {
+                    writeln!(out, "{}", tournament_json(&report))?;
+                } else {
+                    write_tournament(out, &report)?;
+                }
+            }
+            Command::BestMove {
+                board,
+                limits,
+                threads,
+            } => {
+                let best = ai::best_move(&board, limits, threads);
+                if json {
+                    let (column, score) = match best {
+                        Some((column, score)) => (column_json(column), score_json(score)),
+                        None => ("null".into(), "null".into()),
+                    };
+                    writeln!(
+                        out,
+                        r#"{{"moves":"{}","column":{},"score":{}}}"#,
+                        board.to_moves(),
+                        column,
+                        score
+                    )?;
+                } else {
+                    match best {
+                        Some((column, score)) => writeln!(out, "{} ({})", column.to_u8(), score)?,
+                        None => writeln!(out, "none")?,
+                    }
+                }
+            }
+            Command::Solve { board } => {
+                let mut solver = Solver::for_board(&board);
+                let start = Instant::now();
+                let score = solver.solve(&board)?;
+                let millis = start.elapsed().as_millis();
+                if json {
+                    writeln!(
+                        out,
+                        r#"{{"moves":"{}","score":{},"nodes":{},"time_ms":{}}}"#,
+                        board.to_moves(),
+                        score_json(score),
+                        solver.nodes(),
+                        millis
+                    )?;
+                } else {
+                    writeln!(out, "{} ({} nodes, {} ms)", score, solver.nodes(), millis)?;
+                }
+            }
+            Command::Analyze {
+                board,
+                limits,
+                threads,
+            } => {
+                let scores = ai::analyze(&board, limits, threads);
+                if json {
+                    let columns = scores
+                        .iter()
+                        .map(|(column, score)| {
+                            format!(
+                                r#"{{"column":{},"score":{}}}"#,
+                                column_json(*column),
+                                score_json(*score)
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    writeln!(
+                        out,
+                        r#"{{"moves":"{}","columns":[{}]}}"#,
+                        board.to_moves(),
+                        columns
+                    )?;
+                } else {
+                    for (column, score) in scores {
+                        writeln!(out, "{}: {}", column.to_u8(), score)?;
+                    }
+                }
+            }
+            Command::BuildBook { size, rules, plies } => {
+                let book_path = book_path?;
+                // The book starts from the empty board, the largest job the solver gets.
+                let mut solver = Solver::for_board(&ConnectFourBoard::with_rules(size, rules));
+                let start = Instant::now();
+                let book = Book::generate(size, rules, plies, &mut solver, |done, total| {
+                    if done % 100 == 0 || done == total {
+                        eprint!("\rSolved {}/{} positions", done, total);
+                    }
+                })?;
+                eprintln!();
+                if let Some(dir) = book_path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                book.save(&book_path)?;
+                let millis = start.elapsed().as_millis();
+                if json {
+                    writeln!(
+                        out,
+                        r#"{{"positions":{},"plies":{},"time_ms":{}}}"#,
+                        book.len(),
+                        plies,
+                        millis
+                    )?;
+                } else {
+                    writeln!(
+                        out,
+                        "{} positions up to {} plies written to {} ({} ms)",
+                        book.len(),
+                        plies,
+                        book_path.display(),
+                        millis
+                    )?;
+                }
+            }
+            Command::Book { moves } => {
+                let book = Book::load(book_path?)?;
+                let mut board = ConnectFourBoard::with_rules(book.size(), book.rules());
+                board.play_moves(&moves).map_err(CliError::from)?;
+                let score = book.score(&board);
+                let mut columns = book.columns(&board).unwrap_or_default();
+                columns.sort_by_key(|(column, _)| column.to_index());
+                if json {
+                    let columns = columns
+                        .iter()
+                        .map(|(column, score)| {
+                            format!(
+                                r#"{{"column":{},"score":{}}}"#,
+                                column_json(*column),
+                                score_json(*score)
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    writeln!(
+                        out,
+                        r#"{{"size":"{}","connect":{},"plies":{},"positions":{},"moves":"{}","score":{},"columns":[{}]}}"#,
+                        book.size(),
+                        book.rules().connect(),
+                        book.plies(),
+                        book.len(),
+                        board.to_moves(),
+                        score.map_or("null".into(), score_json),
+                        columns
+                    )?;
+                } else {
+                    writeln!(
+                        out,
+                        "{} board, connect {}, {} plies, {} positions",
+                        book.size(),
+                        book.rules().connect(),
+                        book.plies(),
+                        book.len()
+                    )?;
+                    match score {
+                        Some(score) => writeln!(out, "position: {}", score)?,
+                        None => writeln!(out, "position: not in book")?,
+                    }
+                    for (column, score) in columns {
+                        writeln!(out, "{}: {}", column.to_u8(), score)?;
+                    }
+                }
+            }
+            Command::Bench {
+                depth,
+                evaluation,
+                threads,
+            } => {
+                let evaluator = evaluation.evaluator();
+                let report = ai::bench(depth, &*evaluator, threads);
+                // Parallel runs are compared against a single thread searching the same
+                // positions.
+                let speedup = match report.threads {
+                    1 => 1.0,
+                    _ => {
+                        let baseline = ai::bench(depth, &*evaluator, 1);
+                        baseline.elapsed.as_secs_f64() / report.elapsed.as_secs_f64().max(1e-9)
+                    }
+                };
+                if json {
+                    writeln!(out, "{}", bench_json(&report, speedup))?;
+                } else {
+                    write!(
+                        out,
+                        "depth {}: {} positions, {} nodes, {} ms, {} nodes/s",
+                        report.depth,
+                        report.positions,
+                        report.nodes,
+                        report.elapsed.as_millis(),
+                        report.nodes_per_second()
+                    )?;
+                    if report.threads > 1 {
+                        write!(
+                            out,
+                            ", {} threads {:.2}x as fast as 1",
+                            report.threads, speedup
+                        )?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn option_value<T: std::str::FromStr>(
+    option: &'static str,
+    value: Option<String>,
+) -> Result<T, CliError> {
+    let value = value.ok_or(CliError::MissingValue(option))?;
+    value
+        .parse()
+        .map_err(|_| CliError::InvalidValue(option, value))
+}
+
+fn column_json(column: Column) -> String {
+    column.to_u8().to_string()
+}
+
+fn score_json(score: Score) -> String {
+    match score {
+        Score::Win(moves) => format!(r#"{{"result":"win","moves":{}}}"#, moves),
+        Score::Loss(moves) => format!(r#"{{"result":"loss","moves":{}}}"#, moves),
+        Score::Draw => r#"{"result":"draw"}"#.into(),
+        Score::Eval(value) => format!(r#"{{"result":"eval","value":{}}}"#, value),
+    }
+}
+
+fn bench_json(report: &BenchReport, speedup: f64) -> String {
+    format!(
+        r#"{{"depth":{},"threads":{},"positions":{},"nodes":{},"time_ms":{},"nodes_per_second":{},"speedup":{:.2}}}"#,
+        report.depth,
+        report.threads,
+        report.positions,
+        report.nodes,
+        report.elapsed.as_millis(),
+        report.nodes_per_second(),
+        speedup
+    )
+}
+
+fn write_tournament(out: &mut impl Write, report: &TournamentReport) -> io::Result<()> {
+    let width = report
+        .players
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or_default()
+        .max("Player".len());
+    let elo = report.elo();
+
+    writeln!(
+        out,
+        "{:width$}  {:>5}  {:>4}  {:>4}  {:>4}  {:>6}  {:>5}",
+        "Player", "Games", "W", "D", "L", "Score", "Elo"
+    )?;
+    for (player, name) in report.players.iter().enumerate() {
+        let total = report.total(player);
+        writeln!(
+            out,
+            "{:width$}  {:>5}  {:>4}  {:>4}  {:>4}  {:>6.1}  {:>+5.0}",
+            name,
+            total.games(),
+            total.wins,
+            total.draws,
+            total.losses,
+            total.points(),
+            elo[player]
+        )?;
+    }
+
+    // Cross table of wins, draws and losses of each row's player against each column's.
+    writeln!(out)?;
+    write!(out, "{:width$}", "")?;
+    for name in &report.players {
+        write!(out, "  {:>width$}", name)?;
+    }
+    writeln!(out)?;
+    for (player, name) in report.players.iter().enumerate() {
+        write!(out, "{:width$}", name)?;
+        for (opponent, record) in report.records[player].iter().enumerate() {
+            let cell = if player == opponent {
+                "-".to_string()
+            } else {
+                record.to_string()
+            };
+            write!(out, "  {:>width$}", cell)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn tournament_json(report: &TournamentReport) -> String {
+    let elo = report.elo();
+    let players = report
+        .players
+        .iter()
+        .enumerate()
+        .map(|(player, name)| {
+            let total = report.total(player);
+            let records = report.records[player]
+                .iter()
+                .enumerate()
+                .filter(|(opponent, _)| *opponent != player)
+                .map(|(opponent, record)| {
+                    format!(
+                        r#"{{"opponent":"{}","wins":{},"draws":{},"losses":{}}}"#,
+                        report.players[opponent], record.wins, record.draws, record.losses
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(
+                r#"{{"name":"{}","wins":{},"draws":{},"losses":{},"elo":{:.1},"records":[{}]}}"#,
+                name, total.wins, total.draws, total.losses, elo[player], records
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(r#"{{"players":[{}]}}"#, players)
+}
+
+#[cfg(test)]
+mod tests {
+    use connect_fors::{
+        ai::{Evaluation, SearchLimits},
+        game::{BoardSize, ParseError, Rules, Variant},
+    };
+
+    use super::{Cli, CliError, Command, Format};
+
+    fn parse(args: &str) -> Result<Cli, CliError> {
+        Cli::parse(args.split_whitespace().map(String::from))
+    }
+
+    fn output(args: &str) -> String {
+        let mut out = Vec::new();
+        parse(args).unwrap().run(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn defaults_to_play() {
+        assert!(matches!(
+            parse("").unwrap().command,
+            Command::Play { engine: None, size, rules, threads: 1 }
+                if size == BoardSize::STANDARD && rules == Rules::STANDARD
+        ));
+        assert!(matches!(
+            parse("play --engine ./bot").unwrap().command,
+            Command::Play { engine: Some(engine), .. } if engine == "./bot"
+        ));
+        assert!(matches!(
+            parse("play --size 9x7").unwrap().command,
+            Command::Play { size, .. } if size == BoardSize::new(9, 7).unwrap()
+        ));
+        assert!(matches!(
+            parse("play --threads 4").unwrap().command,
+            Command::Play { threads: 4, .. }
+        ));
+        assert!(matches!(
+            parse("play --variant popout --connect 5").unwrap().command,
+            Command::Play { rules, .. } if rules == Rules::new(Variant::PopOut, 5).unwrap()
+        ));
+    }
+
+    #[test]
+    fn parses_analysis_commands() {
+        let cli = parse("analyze 4453 --time 250 --json --threads 2").unwrap();
+        assert_eq!(Format::Json, cli.format);
+        match cli.command {
+            Command::Analyze {
+                board,
+                limits,
+                threads,
+            } => {
+                assert_eq!("4453", board.to_moves());
+                assert_eq!(
+                    SearchLimits::Time(std::time::Duration::from_millis(250)),
+                    limits
+                );
+                assert_eq!(2, threads);
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+        assert!(matches!(
+            parse("best-move").unwrap().command,
+            Command::BestMove { threads: 1, .. }
+        ));
+        assert!(matches!(
+            parse("engine --threads 3").unwrap().command,
+            Command::Engine { threads: 3 }
+        ));
+        match parse("solve 4455 --connect 3").unwrap().command {
+            Command::Solve { board } => assert_eq!(3, board.connect()),
+            command => panic!("unexpected command {:?}", command),
+        }
+        assert!(matches!(
+            parse("bench --depth 6").unwrap().command,
+            Command::Bench {
+                depth: 6,
+                evaluation: Evaluation::Table,
+                threads: 1
+            }
+        ));
+        assert!(matches!(
+            parse("bench --threads 4").unwrap().command,
+            Command::Bench { threads: 4, .. }
+        ));
+        assert!(matches!(
+            parse("bench --eval threats").unwrap().command,
+            Command::Bench {
+                evaluation: Evaluation::Threats,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_tournament() {
+        let cli = parse("tournament depth=2 depth=4,threats=8 --rounds 3 --threads 2").unwrap();
+        match cli.command {
+            Command::Tournament { players, options } => {
+                let names: Vec<_> = players.iter().map(|player| player.name.as_str()).collect();
+                assert_eq!(["depth=2", "depth=4,threats=8"], names[..]);
+                assert_eq!(3, options.rounds);
+                assert_eq!(2, options.threads);
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+        assert_eq!(
+            CliError::UnknownOption("--rounds".into()),
+            parse("analyze --rounds 3").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--threads".into()),
+            parse("solve 44 --threads 2").unwrap_err()
+        );
+        assert!(matches!(
+            parse("tournament depth=2 depth").unwrap_err(),
+            CliError::InvalidPlayer(_)
+        ));
+    }
+
+    #[test]
+    fn parses_book_commands() {
+        match parse("build-book --plies 4 --size 6x5 --connect 3")
+            .unwrap()
+            .command
+        {
+            Command::BuildBook { size, rules, plies } => {
+                assert_eq!(BoardSize::new(6, 5).unwrap(), size);
+                assert_eq!(3, rules.connect());
+                assert_eq!(4, plies);
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+        let cli = parse("book 4453 --book openings.bin").unwrap();
+        assert!(matches!(cli.command, Command::Book { moves } if moves == "4453"));
+        assert_eq!(Some("openings.bin".into()), cli.book);
+        assert_eq!(
+            CliError::UnknownOption("--plies".into()),
+            parse("book --plies 4").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--book".into()),
+            parse("solve 44 --book openings.bin").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--variant".into()),
+            parse("build-book --variant popout").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn error_parsing_invalid_arguments() {
+        assert_eq!(
+            CliError::UnknownCommand("hint".into()),
+            parse("hint").unwrap_err()
+        );
+        assert_eq!(
+            CliError::InvalidMoves(ParseError::InvalidColumn('8')),
+            parse("solve 48").unwrap_err()
+        );
+        assert_eq!(
+            CliError::MissingValue("--depth"),
+            parse("best-move 44 --depth").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--time".into()),
+            parse("solve 44 --time 10").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnexpectedArgument("44".into()),
+            parse("bench 44").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--engine".into()),
+            parse("analyze 44 --engine ./bot").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--eval".into()),
+            parse("solve 44 --eval threats").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--variant".into()),
+            parse("analyze 44 --variant popout").unwrap_err()
+        );
+        assert_eq!(
+            CliError::InvalidValue("--connect", "6".into()),
+            parse("play --connect 6").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--connect".into()),
+            parse("bench --connect 3").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnknownOption("--perfect".into()),
+            parse("bench --perfect").unwrap_err()
+        );
+        assert_eq!(
+            CliError::UnexpectedArgument("5".into()),
+            parse("analyze 44 5").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn prints_best_move() {
+        // Player one can win in the last column, while player two threatens two others.
+        assert_eq!(
+            "7 (mate in 1)\n",
+            output("best-move 767676353525 --depth 4")
+        );
+        assert_eq!(
+            r#"{"moves":"767676353525","column":7,"score":{"result":"win","moves":1}}"#,
+            output("best-move 767676353525 --depth 4 --json").trim_end()
+        );
+        assert_eq!(
+            r#"{"moves":"1212121","column":null,"score":null}"#,
+            output("best-move 1212121 --json").trim_end()
+        );
+    }
+
+    #[test]
+    fn prints_analysis() {
+        let text = output("analyze 767676353525 --depth 4");
+        assert_eq!(7, text.lines().count());
+        assert!(text.starts_with("1: mated in 1\n"));
+        assert!(text.ends_with("6: mated in 1\n7: mate in 1\n"));
+
+        let json = output("analyze 1111112 --depth 2 --json");
+        assert!(json.starts_with(r#"{"moves":"1111112","columns":[{"column":2,"score":"#));
+    }
+
+    #[test]
+    fn builds_and_inspects_book() {
+        let path = std::env::temp_dir().join(format!("connect-fors-{}.book", std::process::id()));
+        let path = path.display();
+        assert!(
+            output(&format!("build-book --size 5x4 --plies 2 --book {}", path))
+                .starts_with(&format!("17 positions up to 2 plies written to {}", path))
+        );
+        assert_eq!(
+            "5x4 board, connect 4, 2 plies, 17 positions\nposition: draw\n",
+            output(&format!("book 33 --book {}", path))
+        );
+        let json = output(&format!("book 3 --book {} --json", path));
+        assert!(json.starts_with(
+            r#"{"size":"5x4","connect":4,"plies":2,"positions":17,"moves":"3","score":{"result":"#
+        ));
+        assert!(json.contains(r#""columns":[{"column":1,"score":"#));
+        std::fs::remove_file(path.to_string()).unwrap();
+    }
+
+    #[test]
+    fn prints_solved_score() {
+        let json = output("solve 767676353525 --json");
+        assert!(
+            json.starts_with(
+                r#"{"moves":"767676353525","score":{"result":"win","moves":1},"nodes":"#
+            )
+        );
+    }
+}
@@ -0,0 +1,850 @@
+//! Line based text protocol that lets other programs drive the AI over stdin and stdout,
+//! in the spirit of UCI for chess engines.
+//!
+//! Commands read by the engine:
+//!
+//! - `position moves <moves>` sets up the board from column digits played in order, such
+//!   as `position moves 4453`. Leaving out the moves sets up an empty board. Boards other
+//!   than 7x6 give their size first, as in `position size 8x7 moves 4453`, PopOut
+//!   games their variant, as in `position variant popout moves 4453p4`, and games played
+//!   for lines other than four their length, as in `position connect 5 moves 4453`.
+//! - `go depth <plies>` searches to a fixed depth.
+//! - `go movetime <ms>` searches for a fixed amount of time. Both limits can be combined,
+//!   and a plain `go` searches until `stop` is received.
+//! - `stop` ends the current search early. A `position` or `go` received while searching
+//!   ends the current search as well, before it is carried out.
+//! - `isready` is answered with `readyok`, even while searching.
+//! - `quit` ends the current search and exits.
+//!
+//! Lines written by the engine:
+//!
+//! - `info depth <plies> score <score> nodes <count> time <ms> pv <moves>` after every
+//!   completed depth, where the score is `eval <value>`, `mate <moves>` when the player
+//!   to move forces a win, or `mate -<moves>` when they are forced to lose.
+//! - `bestmove <move>` once a search ends, or `bestmove none` when the game is over.
+//! - `readyok` in reply to `isready`.
+//! - `error <reason>` for lines that could not be understood.
+
+use std::{
+    ffi::OsStr,
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread::{self, ScopedJoinHandle},
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::{
+    ai::{self, Score, SearchInfo, StopHandle, Strategy, TranspositionTable},
+    game::{BoardSize, ConnectFourBoard, MAX_SLOTS, Move, ParseError, Rules, Variant},
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum EngineError {
+    #[error("Unknown command '{0}'.")]
+    UnknownCommand(String),
+
+    #[error("Command '{0}' is missing its arguments.")]
+    MissingArgument(String),
+
+    #[error("Invalid argument '{0}'.")]
+    InvalidArgument(String),
+
+    #[error("Invalid move sequence. {0}")]
+    InvalidMoves(#[from] ParseError),
+}
+
+/// Failure talking to an engine running in another process.
+#[derive(Error, Debug)]
+pub enum ExternalEngineError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("Engine sent an unrecognized reply: {0}")]
+    InvalidReply(String),
+
+    #[error("Engine replied with move {0}, which is not legal.")]
+    IllegalMove(Move),
+
+    #[error("Engine reported an error: {0}")]
+    Rejected(String),
+
+    #[error("Engine exited.")]
+    Disconnected,
+
+    #[error("Engine did not report that it is ready in time.")]
+    NotReady,
+}
+
+/// Limits of a `go` command. A search without any limits runs until it is stopped.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GoLimits {
+    pub depth: Option<u8>,
+    pub movetime: Option<Duration>,
+}
+
+/// A single line read by the engine.
+#[derive(Debug, Clone)]
+pub enum EngineCommand {
+    Position(ConnectFourBoard),
+    Go(GoLimits),
+    Stop,
+    IsReady,
+    Quit,
+}
+
+impl FromStr for EngineCommand {
+    type Err = EngineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        match command {
+            "position" => {
+                let mut size = BoardSize::STANDARD;
+                let mut variant = Variant::Standard;
+                let mut connect = Rules::STANDARD.connect();
+                loop {
+                    match words.next() {
+                        Some("moves") => break,
+                        Some(option @ ("size" | "variant" | "connect")) => {
+                            let value = words
+                                .next()
+                                .ok_or_else(|| EngineError::MissingArgument(option.into()))?;
+                            let invalid = || EngineError::InvalidArgument(value.into());
+                            match option {
+                                "size" => size = value.parse().map_err(|_| invalid())?,
+                                "variant" => variant = value.parse().map_err(|_| invalid())?,
+                                _ => connect = value.parse().map_err(|_| invalid())?,
+                            }
+                        }
+                        Some(word) => return Err(EngineError::InvalidArgument(word.into())),
+                        None => return Err(EngineError::MissingArgument(command.into())),
+                    }
+                }
+                // Moves may also be separated by spaces, as in `position moves 4 4 5 3`.
+                let rules = Rules::new(variant, connect)
+                    .map_err(|_| EngineError::InvalidArgument(connect.to_string()))?;
+                let mut board = ConnectFourBoard::with_rules(size, rules);
+                board.play_moves(&words.collect::<String>())?;
+                Ok(EngineCommand::Position(board))
+            }
+            "go" => {
+                let mut limits = GoLimits::default();
+                while let Some(word) = words.next() {
+                    let value = words
+                        .next()
+                        .ok_or_else(|| EngineError::MissingArgument(word.into()))?;
+                    let invalid = || EngineError::InvalidArgument(value.into());
+                    match word {
+                        "depth" => limits.depth = Some(value.parse().map_err(|_| invalid())?),
+                        "movetime" => {
+                            let millis = value.parse().map_err(|_| invalid())?;
+                            limits.movetime = Some(Duration::from_millis(millis));
+                        }
+                        _ => return Err(EngineError::InvalidArgument(word.into())),
+                    }
+                }
+                Ok(EngineCommand::Go(limits))
+            }
+            "stop" => Ok(EngineCommand::Stop),
+            "isready" => Ok(EngineCommand::IsReady),
+            "quit" => Ok(EngineCommand::Quit),
+            _ => Err(EngineError::UnknownCommand(s.trim().into())),
+        }
+    }
+}
+
+impl fmt::Display for EngineCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Standard games leave out the variant, size and connect, so engines that predate them
+            // still work.
+            EngineCommand::Position(board) => {
+                write!(f, "position")?;
+                if board.variant() != Variant::Standard {
+                    write!(f, " variant {}", board.variant())?;
+                }
+                if board.size() != BoardSize::STANDARD {
+                    write!(f, " size {}", board.size())?;
+                }
+                if board.connect() != Rules::STANDARD.connect() {
+                    write!(f, " connect {}", board.connect())?;
+                }
+                write!(f, " moves {}", board.to_moves())
+            }
+            EngineCommand::Go(limits) => {
+                write!(f, "go")?;
+                if let Some(depth) = limits.depth {
+                    write!(f, " depth {}", depth)?;
+                }
+                if let Some(movetime) = limits.movetime {
+                    write!(f, " movetime {}", movetime.as_millis())?;
+                }
+                Ok(())
+            }
+            EngineCommand::Stop => write!(f, "stop"),
+            EngineCommand::IsReady => write!(f, "isready"),
+            EngineCommand::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+/// A single line written by the engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineReply {
+    Info(SearchInfo),
+    BestMove(Option<Move>),
+    ReadyOk,
+    Error(String),
+}
+
+impl fmt::Display for EngineReply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineReply::Info(info) => {
+                write!(f, "info depth {} score ", info.depth)?;
+                match info.score {
+                    Score::Win(moves) => write!(f, "mate {}", moves)?,
+                    Score::Loss(moves) => write!(f, "mate -{}", moves)?,
+                    Score::Draw => write!(f, "eval 0")?,
+                    Score::Eval(value) => write!(f, "eval {}", value)?,
+                }
+                write!(
+                    f,
+                    " nodes {} time {} pv",
+                    info.nodes,
+                    info.elapsed.as_millis()
+                )?;
+                for pv_move in &info.pv {
+                    write!(f, " {}", pv_move)?;
+                }
+                Ok(())
+            }
+            EngineReply::BestMove(Some(best_move)) => write!(f, "bestmove {}", best_move),
+            EngineReply::BestMove(None) => write!(f, "bestmove none"),
+            EngineReply::ReadyOk => write!(f, "readyok"),
+            EngineReply::Error(reason) => write!(f, "error {}", reason),
+        }
+    }
+}
+
+impl FromStr for EngineReply {
+    type Err = ExternalEngineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ExternalEngineError::InvalidReply(s.to_string());
+        let (command, argument) = s.split_once(' ').unwrap_or((s, ""));
+        match command {
+            "info" => parse_info(argument)
+                .map(EngineReply::Info)
+                .ok_or_else(invalid),
+            "bestmove" if argument == "none" => Ok(EngineReply::BestMove(None)),
+            "bestmove" => argument
+                .parse()
+                .map(|best_move| EngineReply::BestMove(Some(best_move)))
+                .map_err(|_| invalid()),
+            "readyok" => Ok(EngineReply::ReadyOk),
+            "error" => Ok(EngineReply::Error(argument.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn parse_info(s: &str) -> Option<SearchInfo> {
+    let mut words = s.split_whitespace();
+    let (mut depth, mut score, mut nodes, mut elapsed) = (None, None, None, None);
+    let mut pv = Vec::new();
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => depth = Some(words.next()?.parse().ok()?),
+            "score" => {
+                let kind = words.next()?;
+                let value: i32 = words.next()?.parse().ok()?;
+                score = Some(match kind {
+                    "eval" => Score::Eval(value),
+                    "mate" if value > 0 => Score::Win(value as u8),
+                    "mate" => Score::Loss(-value as u8),
+                    _ => return None,
+                });
+            }
+            "nodes" => nodes = Some(words.next()?.parse().ok()?),
+            "time" => elapsed = Some(Duration::from_millis(words.next()?.parse().ok()?)),
+            // The principal variation runs to the end of the line.
+            "pv" => {
+                pv = words
+                    .by_ref()
+                    .map(|pv_move| pv_move.parse().ok())
+                    .collect::<Option<_>>()?
+            }
+            _ => return None,
+        }
+    }
+
+    Some(SearchInfo {
+        depth: depth?,
+        score: score?,
+        nodes: nodes?,
+        elapsed: elapsed?,
+        pv,
+    })
+}
+
+/// Reads commands from `input` until it ends or `quit` is received, writing replies to
+/// `output`. Searches run on `threads` worker threads so `stop` and `isready` are answered
+/// while the engine is thinking.
+pub fn run(input: impl BufRead, output: impl Write + Send, threads: usize) -> io::Result<()> {
+    let output = Mutex::new(output);
+    let table = Mutex::new(TranspositionTable::default());
+    let stop = AtomicBool::new(false);
+    let send = |reply: &EngineReply| -> io::Result<()> {
+        let mut output = output.lock().unwrap();
+        writeln!(output, "{}", reply)?;
+        output.flush()
+    };
+
+    thread::scope(|scope| {
+        let mut board = ConnectFourBoard::default();
+        let mut search: Option<ScopedJoinHandle<io::Result<()>>> = None;
+        // Stops the running search and waits for it to send its best move. Commands that
+        // replace the position or start a new search cut the running one short, so the
+        // commands after them keep being read.
+        let finish = |search: &mut Option<ScopedJoinHandle<_>>| match search.take() {
+            Some(handle) => {
+                stop.store(true, Ordering::Relaxed);
+                handle.join().unwrap()
+            }
+            None => Ok(()),
+        };
+
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match line.parse() {
+                Ok(EngineCommand::Position(position)) => {
+                    finish(&mut search)?;
+                    board = position;
+                }
+                Ok(EngineCommand::Go(limits)) => {
+                    finish(&mut search)?;
+                    stop.store(false, Ordering::Relaxed);
+                    let board = board.clone();
+                    let (table, stop, send) = (&table, &stop, &send);
+                    search = Some(scope.spawn(move || {
+                        let mut table = table.lock().unwrap();
+                        let mut result = Ok(());
+                        let best_move = ai::think(
+                            &board,
+                            limits.depth.unwrap_or(MAX_SLOTS).max(1),
+                            limits.movetime,
+                            &mut table,
+                            Some(stop),
+                            threads,
+                            |info| {
+                                if result.is_ok() {
+                                    result = send(&EngineReply::Info(info.clone()));
+                                }
+                            },
+                        );
+                        result.and_then(|_| send(&EngineReply::BestMove(best_move)))
+                    }));
+                }
+                Ok(EngineCommand::Stop) => finish(&mut search)?,
+                Ok(EngineCommand::IsReady) => send(&EngineReply::ReadyOk)?,
+                Ok(EngineCommand::Quit) => break,
+                Err(err) => send(&EngineReply::Error(err.to_string()))?,
+            }
+        }
+
+        finish(&mut search)
+    })
+}
+
+/// How long a freshly started engine gets to answer `isready`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Engine running in a child process that speaks the protocol, such as another build of
+/// this program started with `connect-fors engine`.
+pub struct ExternalEngine {
+    name: String,
+    child: Mutex<Child>,
+    stdin: Mutex<ChildStdin>,
+    stdout: Mutex<BufReader<ChildStdout>>,
+}
+
+impl ExternalEngine {
+    /// Starts `program` and waits until it reports that it is ready, giving up on programs
+    /// that do not within `HANDSHAKE_TIMEOUT`.
+    pub fn spawn(
+        program: impl AsRef<OsStr>,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> Result<Self, ExternalEngineError> {
+        let program = program.as_ref();
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("engine stdin is piped");
+        let stdout = child.stdout.take().expect("engine stdout is piped");
+
+        let stdout = match handshake(&mut stdin, stdout, HANDSHAKE_TIMEOUT) {
+            Ok(stdout) => stdout,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err);
+            }
+        };
+        Ok(Self {
+            name: Path::new(program)
+                .file_name()
+                .unwrap_or(program)
+                .to_string_lossy()
+                .into_owned(),
+            child: Mutex::new(child),
+            stdin: Mutex::new(stdin),
+            stdout: Mutex::new(stdout),
+        })
+    }
+
+    /// File name of the engine's program.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sends the position and waits for the engine's move, letting it search for at most
+    /// `movetime`. Returns `None` when the engine finds no move to play.
+    pub fn best_move(
+        &self,
+        board: &ConnectFourBoard,
+        movetime: Duration,
+    ) -> Result<Option<Move>, ExternalEngineError> {
+        // Holding on to the output until the best move arrives keeps the replies of
+        // consecutive searches from getting mixed up.
+        let mut stdout = self.stdout.lock().unwrap();
+        self.send(&EngineCommand::Position(board.clone()))?;
+        self.send(&EngineCommand::Go(GoLimits {
+            depth: None,
+            movetime: Some(movetime),
+        }))?;
+        loop {
+            match receive(&mut stdout)? {
+                EngineReply::BestMove(Some(best_move)) if !board.is_legal(best_move) => {
+                    return Err(ExternalEngineError::IllegalMove(best_move));
+                }
+                EngineReply::BestMove(best_move) => return Ok(best_move),
+                EngineReply::Error(reason) => return Err(ExternalEngineError::Rejected(reason)),
+                EngineReply::Info(_) | EngineReply::ReadyOk => {}
+            }
+        }
+    }
+
+    /// Asks the engine to cut its current search short, so `best_move` returns early.
+    pub fn stop(&self) -> Result<(), ExternalEngineError> {
+        self.send(&EngineCommand::Stop)
+    }
+
+    fn send(&self, command: &EngineCommand) -> Result<(), ExternalEngineError> {
+        let mut stdin = self.stdin.lock().unwrap();
+        writeln!(stdin, "{}", command)?;
+        stdin.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send(&EngineCommand::Quit);
+        let child = self.child.get_mut().unwrap();
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Plays the moves of an external engine, which gets `movetime` to think about each one.
+/// Failing to get a reply from the engine counts as having no move to offer, and the
+/// reason is kept for `Strategy::take_error`.
+pub struct EngineStrategy {
+    engine: Arc<ExternalEngine>,
+    movetime: Duration,
+    /// Set when the next search is stopped before it reaches the engine.
+    stopped: Arc<AtomicBool>,
+    error: Option<ExternalEngineError>,
+}
+
+impl EngineStrategy {
+    pub fn new(engine: Arc<ExternalEngine>, movetime: Duration) -> Self {
+        Self {
+            engine,
+            movetime,
+            stopped: Arc::new(AtomicBool::new(false)),
+            error: None,
+        }
+    }
+}
+
+impl Strategy for EngineStrategy {
+    fn name(&self) -> String {
+        self.engine.name().to_string()
+    }
+
+    fn choose(&mut self, board: &ConnectFourBoard) -> Option<Move> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.engine
+            .best_move(board, self.movetime)
+            .unwrap_or_else(|err| {
+                self.error = Some(err);
+                None
+            })
+    }
+
+    fn stop_handle(&mut self) -> Option<StopHandle> {
+        self.stopped = Arc::new(AtomicBool::new(false));
+        let (engine, stopped) = (Arc::clone(&self.engine), Arc::clone(&self.stopped));
+        Some(Arc::new(move || {
+            stopped.store(true, Ordering::Relaxed);
+            let _ = engine.stop();
+        }))
+    }
+
+    fn take_error(&mut self) -> Option<String> {
+        self.error.take().map(|err| err.to_string())
+    }
+}
+
+/// Asks a freshly started engine whether it is ready and waits up to `timeout` for it to
+/// say so. The replies are read on a thread of their own, which ends once the engine
+/// exits, so an engine that never answers can not hang the caller.
+fn handshake(
+    stdin: &mut ChildStdin,
+    stdout: ChildStdout,
+    timeout: Duration,
+) -> Result<BufReader<ChildStdout>, ExternalEngineError> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdout = BufReader::new(stdout);
+        let ready = loop {
+            match receive(&mut stdout) {
+                Ok(EngineReply::ReadyOk) => break Ok(stdout),
+                Ok(_) => {}
+                Err(err) => break Err(err),
+            }
+        };
+        let _ = sender.send(ready);
+    });
+
+    writeln!(stdin, "{}", EngineCommand::IsReady)?;
+    stdin.flush()?;
+    receiver
+        .recv_timeout(timeout)
+        .unwrap_or(Err(ExternalEngineError::NotReady))
+}
+
+/// Blocks until the next reply arrives. An engine that exited is reported as
+/// `ExternalEngineError::Disconnected`.
+fn receive(stdout: &mut BufReader<ChildStdout>) -> Result<EngineReply, ExternalEngineError> {
+    let mut line = String::new();
+    if stdout.read_line(&mut line)? == 0 {
+        return Err(ExternalEngineError::Disconnected);
+    }
+    line.trim_end().parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process::{Command, Stdio},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use crate::{
+        ai::{Score, SearchInfo, Strategy},
+        game::{Column, ConnectFourBoard, Move, ParseError, Variant},
+    };
+
+    use super::{
+        EngineCommand, EngineError, EngineReply, EngineStrategy, ExternalEngine,
+        ExternalEngineError, GoLimits, handshake, run,
+    };
+
+    // Stands in for a real engine by running the same shell command for every search.
+    fn stub_engine(on_go: &str) -> ExternalEngine {
+        let script = format!(
+            r#"while read -r command args; do
+                case "$command" in
+                    isready) echo readyok ;;
+                    go) echo "info depth 1 score eval 0 nodes 1 time 0 pv 4"; {} ;;
+                    quit) exit ;;
+                esac
+            done"#,
+            on_go
+        );
+        ExternalEngine::spawn("sh", ["-c", &script]).unwrap()
+    }
+
+    fn output(input: &str) -> String {
+        output_with_threads(input, 1)
+    }
+
+    fn output_with_threads(input: &str, threads: usize) -> String {
+        let mut out = Vec::new();
+        run(input.as_bytes(), &mut out, threads).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parses_commands() {
+        match "position moves 4453".parse().unwrap() {
+            EngineCommand::Position(board) => assert_eq!("4453", board.to_moves()),
+            command => panic!("unexpected command {:?}", command),
+        }
+        match "position moves 4 4 5".parse().unwrap() {
+            EngineCommand::Position(board) => assert_eq!("445", board.to_moves()),
+            command => panic!("unexpected command {:?}", command),
+        }
+        match "position size 9x7 moves 98"
+            .parse::<EngineCommand>()
+            .unwrap()
+        {
+            EngineCommand::Position(board) => {
+                assert_eq!("9x7", board.size().to_string());
+                assert_eq!("98", board.to_moves());
+                assert_eq!(
+                    "position size 9x7 moves 98",
+                    EngineCommand::Position(board).to_string()
+                );
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+        match "position variant popout size 8x7 moves 44p4"
+            .parse::<EngineCommand>()
+            .unwrap()
+        {
+            EngineCommand::Position(board) => {
+                assert_eq!(Variant::PopOut, board.variant());
+                assert_eq!(
+                    "position variant popout size 8x7 moves 44p4",
+                    EngineCommand::Position(board).to_string()
+                );
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+        match "position connect 3 moves 4455"
+            .parse::<EngineCommand>()
+            .unwrap()
+        {
+            EngineCommand::Position(board) => {
+                assert_eq!(3, board.connect());
+                assert_eq!(
+                    "position connect 3 moves 4455",
+                    EngineCommand::Position(board).to_string()
+                );
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+        assert!(matches!(
+            "position connect 6 moves 4".parse::<EngineCommand>(),
+            Err(EngineError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            "go depth 12 movetime 500".parse().unwrap(),
+            EngineCommand::Go(GoLimits {
+                depth: Some(12),
+                movetime: Some(time),
+            }) if time == Duration::from_millis(500)
+        ));
+        assert!(matches!(
+            "go".parse().unwrap(),
+            EngineCommand::Go(GoLimits {
+                depth: None,
+                movetime: None
+            })
+        ));
+        assert!(matches!("stop".parse().unwrap(), EngineCommand::Stop));
+    }
+
+    #[test]
+    fn error_parsing_invalid_commands() {
+        assert_eq!(
+            EngineError::UnknownCommand("jump 4".into()),
+            "jump 4".parse::<EngineCommand>().unwrap_err()
+        );
+        assert_eq!(
+            EngineError::InvalidMoves(ParseError::InvalidColumn('8')),
+            "position moves 48".parse::<EngineCommand>().unwrap_err()
+        );
+        assert_eq!(
+            EngineError::InvalidArgument("10x6".into()),
+            "position size 10x6 moves 4"
+                .parse::<EngineCommand>()
+                .unwrap_err()
+        );
+        assert_eq!(
+            EngineError::MissingArgument("depth".into()),
+            "go depth".parse::<EngineCommand>().unwrap_err()
+        );
+        assert_eq!(
+            EngineError::InvalidArgument("fast".into()),
+            "go movetime fast".parse::<EngineCommand>().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn formats_info() {
+        let info = SearchInfo {
+            depth: 3,
+            score: Score::Loss(2),
+            nodes: 120,
+            elapsed: Duration::from_millis(4),
+            pv: vec![
+                Move::Drop(Column::Four),
+                Move::Drop(Column::Three),
+                Move::Drop(Column::Four),
+            ],
+        };
+        assert_eq!(
+            "info depth 3 score mate -2 nodes 120 time 4 pv 4 3 4",
+            EngineReply::Info(info).to_string()
+        );
+    }
+
+    #[test]
+    fn searches_position() {
+        // Player one can win in the first column.
+        let out = output("position moves 121212\ngo depth 4\nquit\n");
+        let lines: Vec<_> = out.lines().collect();
+        assert!(lines[0].starts_with("info depth 1 score mate 1 nodes "));
+        assert!(lines[0].ends_with(" pv 1"));
+        assert_eq!(["bestmove 1"], lines[1..]);
+    }
+
+    #[test]
+    fn stops_search() {
+        // Helper threads stop along with the search they help.
+        for threads in [1, 3] {
+            let input = "go\nisready\nstop\nposition moves 1212121\ngo depth 2\n";
+            let out = output_with_threads(input, threads);
+            let lines: Vec<_> = out.lines().collect();
+            assert!(lines.contains(&"readyok"));
+            assert!(lines[lines.len() - 2].starts_with("bestmove "));
+            assert_eq!("bestmove none", lines[lines.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn interrupts_search_for_new_commands() {
+        for (input, searches) in [
+            ("go\nposition moves 4\nisready\n", 1),
+            ("go\ngo depth 2\nisready\n", 2),
+        ] {
+            let out = output(input);
+            let lines: Vec<_> = out.lines().collect();
+            assert!(lines.contains(&"readyok"), "{input}");
+            let best_moves = lines.iter().filter(|line| line.starts_with("bestmove "));
+            assert_eq!(searches, best_moves.count(), "{input}");
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(
+            "error Unknown command 'hello'.\nreadyok\n",
+            output("hello\nisready\n")
+        );
+    }
+
+    #[test]
+    fn parses_replies() {
+        for reply in [
+            EngineReply::Info(SearchInfo {
+                depth: 7,
+                score: Score::Win(3),
+                nodes: 5120,
+                elapsed: Duration::from_millis(12),
+                pv: vec![Move::Drop(Column::Four), Move::Pop(Column::Five)],
+            }),
+            EngineReply::BestMove(Some(Move::Drop(Column::Two))),
+            EngineReply::BestMove(Some(Move::Pop(Column::Six))),
+            EngineReply::BestMove(None),
+            EngineReply::ReadyOk,
+            EngineReply::Error("Unknown command 'hello'.".into()),
+        ] {
+            assert_eq!(reply, reply.to_string().parse().unwrap());
+        }
+        assert!("bestmove 0".parse::<EngineReply>().is_err());
+        assert!("info depth 2".parse::<EngineReply>().is_err());
+    }
+
+    #[test]
+    fn plays_stub_engine_moves() {
+        let engine = stub_engine("echo bestmove 3");
+        assert_eq!("sh", engine.name());
+
+        let mut board = ConnectFourBoard::default();
+        for _ in 0..2 {
+            let best_move = engine.best_move(&board, Duration::from_millis(10)).unwrap();
+            assert_eq!(Some(Move::Drop(Column::Three)), best_move);
+            board.try_play(best_move.unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_stub_engine_errors() {
+        let board = ConnectFourBoard::from_moves("333333").unwrap();
+        assert!(matches!(
+            stub_engine("echo bestmove 3").best_move(&board, Duration::ZERO),
+            Err(ExternalEngineError::IllegalMove(Move::Drop(Column::Three)))
+        ));
+        assert!(matches!(
+            stub_engine("echo error out of memory").best_move(&board, Duration::ZERO),
+            Err(ExternalEngineError::Rejected(reason)) if reason == "out of memory"
+        ));
+        assert!(matches!(
+            stub_engine("exit").best_move(&board, Duration::ZERO),
+            Err(ExternalEngineError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn keeps_stub_engine_errors_for_the_player() {
+        let mut strategy = EngineStrategy::new(Arc::new(stub_engine("exit")), Duration::ZERO);
+        assert_eq!(None, strategy.choose(&ConnectFourBoard::default()));
+        assert_eq!(Some("Engine exited.".into()), strategy.take_error());
+        assert_eq!(None, strategy.take_error());
+    }
+
+    #[test]
+    fn gives_up_on_engines_that_never_get_ready() {
+        let mut child = Command::new("sh")
+            .args(["-c", "cat > /dev/null"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        assert!(matches!(
+            handshake(&mut stdin, stdout, Duration::from_millis(50)),
+            Err(ExternalEngineError::NotReady)
+        ));
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
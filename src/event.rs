@@ -1,10 +1,33 @@
-use std::{sync::mpsc, thread, time::Duration};
-
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use connect_fors::{
+    ai::Strategy,
+    game::Move,
+    net::{Connection, Message, NetError},
+};
 use crossterm::event;
 
 pub enum TerminalEvent {
     Input(Key),
     Tick,
+    /// Move chosen by an AI search running on a worker thread, which hands back the
+    /// strategy it searched with.
+    AiMove {
+        search: u64,
+        ai_move: Option<Move>,
+        strategy: Box<dyn Strategy>,
+    },
+    /// Outcome of hosting or joining a network game.
+    Connected(Result<Connection, NetError>),
+    /// Message received from the peer of a network game.
+    Peer {
+        connection: u64,
+        message: Result<Message, NetError>,
+    },
 }
 
 /// Simple event handler wrapping cross-term input and tick events. Each event
@@ -13,7 +36,7 @@ pub struct TerminalEvents {
     rx: mpsc::Receiver<TerminalEvent>,
 
     // Need to be kept around to prevent disposing the sender side.
-    _tx: mpsc::Sender<TerminalEvent>,
+    tx: mpsc::Sender<TerminalEvent>,
 }
 
 impl TerminalEvents {
@@ -23,19 +46,31 @@ impl TerminalEvents {
         let event_tx = tx.clone();
         let tick_rate = Duration::from_millis(250);
         thread::spawn(move || {
+            let mut last_tick = Instant::now();
             loop {
-                if event::poll(tick_rate).unwrap() {
+                // Input only shortens the wait, ticks keep to their own rate.
+                let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+                #[allow(clippy::collapsible_if)]
+                if event::poll(timeout).unwrap() {
                     if let event::Event::Key(key) = event::read().unwrap() {
                         let key = Key::from(key);
                         event_tx.send(TerminalEvent::Input(key)).unwrap();
                     }
                 }
 
-                event_tx.send(TerminalEvent::Tick).unwrap();
+                if last_tick.elapsed() >= tick_rate {
+                    event_tx.send(TerminalEvent::Tick).unwrap();
+                    last_tick = Instant::now();
+                }
             }
         });
 
-        TerminalEvents { rx, _tx: tx }
+        TerminalEvents { rx, tx }
+    }
+
+    /// Sender for events produced outside of the terminal, such as finished AI searches.
+    pub fn sender(&self) -> mpsc::Sender<TerminalEvent> {
+        self.tx.clone()
     }
 
     pub fn next(&self) -> Result<TerminalEvent, mpsc::RecvError> {
@@ -1,9 +1,21 @@
-use std::{fmt, str::FromStr};
+use std::{
+    fmt,
+    ops::{Add, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, Not, Shl, Shr, Sub},
+    str::FromStr,
+};
 
 use thiserror::Error;
 
-const TOP: [u8; 7] = [6, 13, 20, 27, 34, 41, 48];
-const TOP_BITBOARD: u64 = 0b1000000_1000000_1000000_1000000_1000000_1000000_1000000;
+/// Widest board supported, limited by column digits in move sequences.
+pub const MAX_WIDTH: u8 = 9;
+pub const MAX_HEIGHT: u8 = 8;
+/// Boards need room for four in a row in every direction.
+pub const MIN_SIZE: u8 = 4;
+/// Shortest and longest lines that can be played for.
+pub const MIN_CONNECT: u8 = 3;
+pub const MAX_CONNECT: u8 = 5;
+/// Most slots on any supported board.
+pub const MAX_SLOTS: u8 = MAX_WIDTH * MAX_HEIGHT;
 
 #[derive(Error, Debug, PartialEq)]
 pub enum MoveError {
@@ -12,12 +24,53 @@ pub enum MoveError {
 
     #[error("Moves can not be made on a concluded game board. ")]
     ConcludedGame,
+
+    #[error("Column is not on the board.")]
+    OffBoard,
+
+    #[error("Discs can only be popped out in PopOut games.")]
+    PopNotAllowed,
+
+    #[error("Only a disc of the player to move can be popped out of a column.")]
+    NotOwnDisc,
 }
 
 #[derive(Debug, Error)]
 #[error("Provided value was not a valid column.")]
 pub struct ColumnConversionError;
 
+#[derive(Error, Debug, PartialEq)]
+pub enum ParseError {
+    #[error("'{0}' is not a valid column.")]
+    InvalidColumn(char),
+
+    #[error("Column {0} did not have available space.")]
+    FullColumn(Column),
+
+    #[error("Column {0} was played after the game had concluded.")]
+    ConcludedGame(Column),
+
+    #[error("Column {0} had no disc of the player to move to pop out.")]
+    InvalidPop(Column),
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Unknown variant '{0}'.")]
+pub struct UnknownVariant(String);
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Lines must be between 3 and 5 discs long, not {0}.")]
+pub struct ConnectOutOfRange(pub u8);
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BoardSizeError {
+    #[error("'{0}' is not a board size such as 7x6.")]
+    InvalidFormat(String),
+
+    #[error("Boards must be between 4x4 and 9x8, not {0}x{1}.")]
+    OutOfRange(u8, u8),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Column {
     One,
@@ -27,6 +80,8 @@ pub enum Column {
     Five,
     Six,
     Seven,
+    Eight,
+    Nine,
 }
 
 impl fmt::Display for Column {
@@ -36,18 +91,6 @@ impl fmt::Display for Column {
 }
 
 impl Column {
-    pub const fn all() -> [Column; 7] {
-        [
-            Column::One,
-            Column::Two,
-            Column::Three,
-            Column::Four,
-            Column::Five,
-            Column::Six,
-            Column::Seven,
-        ]
-    }
-
     pub const fn to_index(self) -> usize {
         self.to_u8() as usize - 1
     }
@@ -61,6 +104,8 @@ impl Column {
             Column::Five => 5,
             Column::Six => 6,
             Column::Seven => 7,
+            Column::Eight => 8,
+            Column::Nine => 9,
         }
     }
 }
@@ -77,6 +122,8 @@ impl TryFrom<u8> for Column {
             5 => Ok(Column::Five),
             6 => Ok(Column::Six),
             7 => Ok(Column::Seven),
+            8 => Ok(Column::Eight),
+            9 => Ok(Column::Nine),
             _ => Err(ColumnConversionError),
         }
     }
@@ -94,11 +141,236 @@ impl FromStr for Column {
             "5" => Ok(Column::Five),
             "6" => Ok(Column::Six),
             "7" => Ok(Column::Seven),
+            "8" => Ok(Column::Eight),
+            "9" => Ok(Column::Nine),
             _ => Err(ColumnConversionError),
         }
     }
 }
 
+/// Rules the game is played by.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Instead of dropping a disc, players may pop one of their own discs out of the bottom
+    /// of a column. A pop that connects four for both players wins for the player who
+    /// popped, and a position that comes up for the third time is a draw.
+    PopOut,
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Variant::Standard => write!(f, "standard"),
+            Variant::PopOut => write!(f, "popout"),
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = UnknownVariant;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(Variant::Standard),
+            "popout" => Ok(Variant::PopOut),
+            _ => Err(UnknownVariant(s.into())),
+        }
+    }
+}
+
+impl Variant {
+    /// The following variant, wrapping back around to the first.
+    pub const fn next(self) -> Self {
+        match self {
+            Variant::Standard => Variant::PopOut,
+            Variant::PopOut => Variant::Standard,
+        }
+    }
+}
+
+/// Rules the game is played by: the variant and the number of discs in a row that wins.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rules {
+    variant: Variant,
+    connect: u8,
+}
+
+impl Rules {
+    pub const STANDARD: Self = Self {
+        variant: Variant::Standard,
+        connect: 4,
+    };
+
+    pub fn new(variant: Variant, connect: u8) -> Result<Self, ConnectOutOfRange> {
+        if !(MIN_CONNECT..=MAX_CONNECT).contains(&connect) {
+            return Err(ConnectOutOfRange(connect));
+        }
+        Ok(Self { variant, connect })
+    }
+
+    pub const fn variant(self) -> Variant {
+        self.variant
+    }
+
+    /// Discs in a row needed to win.
+    pub const fn connect(self) -> u8 {
+        self.connect
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+/// A single turn, written as the column digit for drops and with a leading `p` for pops,
+/// such as `4` and `p4`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Move {
+    /// Drops a disc on top of a column.
+    Drop(Column),
+    /// Removes a disc of the player to move from the bottom of a column, moving the rest of
+    /// the column down.
+    Pop(Column),
+}
+
+impl Move {
+    pub const fn column(self) -> Column {
+        match self {
+            Move::Drop(column) | Move::Pop(column) => column,
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::Drop(column) => write!(f, "{}", column.to_u8()),
+            Move::Pop(column) => write!(f, "p{}", column.to_u8()),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = ColumnConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('p') {
+            Some(column) => column.parse().map(Move::Pop),
+            None => s.parse().map(Move::Drop),
+        }
+    }
+}
+
+/// Number of columns and rows of a board.
+///
+/// Bitboards store the slot in column `c` and row `r`, counted from the bottom, at bit
+/// `c * (height + 1) + r`. The extra bit on top of every column stays empty, which keeps
+/// lines from wrapping around into the next column.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoardSize {
+    width: u8,
+    height: u8,
+}
+
+impl BoardSize {
+    pub const STANDARD: Self = Self {
+        width: 7,
+        height: 6,
+    };
+
+    pub fn new(width: u8, height: u8) -> Result<Self, BoardSizeError> {
+        if !(MIN_SIZE..=MAX_WIDTH).contains(&width) || !(MIN_SIZE..=MAX_HEIGHT).contains(&height) {
+            return Err(BoardSizeError::OutOfRange(width, height));
+        }
+        Ok(Self { width, height })
+    }
+
+    pub const fn width(self) -> u8 {
+        self.width
+    }
+
+    pub const fn height(self) -> u8 {
+        self.height
+    }
+
+    pub const fn slots(self) -> u8 {
+        self.width * self.height
+    }
+
+    /// Bits used by every column, including the empty bit on top.
+    pub const fn stride(self) -> u32 {
+        self.height as u32 + 1
+    }
+
+    /// Bits used by all columns together.
+    pub const fn bits(self) -> u32 {
+        self.width as u32 * self.stride()
+    }
+
+    /// Middle column, or the right of the two middle columns on boards of even width.
+    pub fn center(self) -> Column {
+        Column::try_from(self.width / 2 + 1).expect("boards are at most nine columns wide")
+    }
+
+    pub fn columns(self) -> impl Iterator<Item = Column> {
+        (1..=self.width).filter_map(|column| Column::try_from(column).ok())
+    }
+
+    pub fn contains(self, column: Column) -> bool {
+        column.to_u8() <= self.width
+    }
+
+    /// Bottom slot of every column.
+    pub const fn bottom_mask(self) -> u128 {
+        let mut mask = 0;
+        let mut col = 0;
+        while col < self.width as u32 {
+            mask |= 1 << (col * self.stride());
+            col += 1;
+        }
+        mask
+    }
+
+    /// Every slot of the board.
+    pub const fn board_mask(self) -> u128 {
+        self.bottom_mask() * ((1 << self.height) - 1)
+    }
+
+    /// Every slot of a single column.
+    pub const fn column_mask(self, column: Column) -> u128 {
+        ((1 << self.height) - 1) << (column.to_index() as u32 * self.stride())
+    }
+}
+
+impl Default for BoardSize {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl fmt::Display for BoardSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for BoardSize {
+    type Err = BoardSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || BoardSizeError::InvalidFormat(s.to_string());
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        BoardSize::new(
+            width.parse().map_err(|_| invalid())?,
+            height.parse().map_err(|_| invalid())?,
+        )
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Copy, Clone)]
 pub enum Player {
     #[default]
@@ -107,12 +379,19 @@ pub enum Player {
 }
 
 impl Player {
-    const fn from_move_count(count: u8) -> Self {
+    const fn from_move_count(count: u16) -> Self {
         match count & 1 {
             0 => Player::One,
             _ => Player::Two,
         }
     }
+
+    pub const fn opponent(self) -> Self {
+        match self {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        }
+    }
 }
 
 #[derive(Default, Debug, PartialEq, Copy, Clone)]
@@ -125,19 +404,120 @@ pub enum BoardStatus {
 
 #[derive(Debug, Clone)]
 pub struct ConnectFourBoard {
-    player_one_bitboard: u64,
-    player_two_bitboard: u64,
-    move_count: u8,
-    heights: [u8; 7],
-    history: Vec<Column>,
+    size: BoardSize,
+    rules: Rules,
+    player_one_bitboard: u128,
+    player_two_bitboard: u128,
+    move_count: u16,
+    /// Bit the next disc of every column lands on.
+    heights: [u8; MAX_WIDTH as usize],
+    history: Vec<Move>,
+    /// Keys of every position of a PopOut game so far, used to spot repetitions.
+    positions: Vec<u128>,
 }
 
 impl ConnectFourBoard {
-    pub fn slots(&self) -> BoardSlots {
+    pub fn new(size: BoardSize) -> Self {
+        Self::with_rules(size, Rules::STANDARD)
+    }
+
+    pub fn with_rules(size: BoardSize, rules: Rules) -> Self {
+        let mut heights = [0; MAX_WIDTH as usize];
+        for (col, height) in heights.iter_mut().enumerate() {
+            *height = col as u8 * size.stride() as u8;
+        }
+        let positions = match rules.variant() {
+            Variant::Standard => Vec::new(),
+            Variant::PopOut => vec![0],
+        };
+        Self {
+            size,
+            rules,
+            player_one_bitboard: 0,
+            player_two_bitboard: 0,
+            move_count: 0,
+            heights,
+            history: Vec::with_capacity(size.slots() as usize),
+            positions,
+        }
+    }
+
+    /// Builds a standard board by playing a sequence of column digits in order, such as
+    /// `4453`.
+    pub fn from_moves(moves: &str) -> Result<Self, ParseError> {
+        Self::from_moves_with_size(moves, BoardSize::STANDARD)
+    }
+
+    pub fn from_moves_with_size(moves: &str, size: BoardSize) -> Result<Self, ParseError> {
+        let mut board = ConnectFourBoard::new(size);
+        board.play_moves(moves)?;
+        Ok(board)
+    }
+
+    /// Plays a sequence of moves in order, such as `4453` or `44p4` in PopOut games.
+    pub fn play_moves(&mut self, moves: &str) -> Result<(), ParseError> {
+        let mut chars = moves.trim().chars();
+        while let Some(c) = chars.next() {
+            let (pop, c) = match c {
+                'p' => (true, chars.next().ok_or(ParseError::InvalidColumn(c))?),
+                c => (false, c),
+            };
+            let column: Column = c
+                .encode_utf8(&mut [0; 4])
+                .parse()
+                .map_err(|_| ParseError::InvalidColumn(c))?;
+            let next_move = if pop {
+                Move::Pop(column)
+            } else {
+                Move::Drop(column)
+            };
+            self.try_play(next_move).map_err(|err| match err {
+                MoveError::FullColumn => ParseError::FullColumn(column),
+                MoveError::ConcludedGame => ParseError::ConcludedGame(column),
+                MoveError::OffBoard => ParseError::InvalidColumn(c),
+                MoveError::PopNotAllowed => ParseError::InvalidColumn('p'),
+                MoveError::NotOwnDisc => ParseError::InvalidPop(column),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Moves played so far, as accepted by `play_moves`.
+    pub fn to_moves(&self) -> String {
+        self.history.iter().map(Move::to_string).collect()
+    }
+
+    /// Moves played so far, from the first to the last.
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    pub fn size(&self) -> BoardSize {
+        self.size
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.rules.variant()
+    }
+
+    /// Discs in a row needed to win.
+    pub fn connect(&self) -> u8 {
+        self.rules.connect()
+    }
+
+    pub fn slots(&self) -> BoardSlots<'_> {
         BoardSlots::new(self)
     }
 
-    pub fn try_move(&mut self, column: Column) -> Result<u64, MoveError> {
+    pub fn try_move(&mut self, column: Column) -> Result<u128, MoveError> {
+        if !self.size.contains(column) {
+            return Err(MoveError::OffBoard);
+        }
+
         if self.status() != BoardStatus::OnGoing {
             return Err(MoveError::ConcludedGame);
         }
@@ -153,85 +533,512 @@ impl ConnectFourBoard {
             Player::Two => self.player_two_bitboard ^= next_position,
         };
 
-        self.move_count += 1;
         self.heights[idx] += 1;
-        self.history.push(column);
+        self.finish_move(Move::Drop(column));
 
         Ok(next_position)
     }
 
-    pub fn pop_move(&mut self) {
-        if let Some(column) = self.history.pop() {
-            let idx = column.to_index();
-            self.move_count -= 1;
-            self.heights[idx] -= 1;
+    /// Pops the disc of the player to move out of the bottom of `column`, returning the
+    /// slot it was popped from.
+    pub fn try_pop(&mut self, column: Column) -> Result<u128, MoveError> {
+        if !self.size.contains(column) {
+            return Err(MoveError::OffBoard);
+        }
 
-            let old_position = 1 << self.heights[idx];
-            match self.current_player() {
-                Player::One => self.player_one_bitboard ^= old_position,
-                Player::Two => self.player_two_bitboard ^= old_position,
-            };
+        if self.variant() != Variant::PopOut {
+            return Err(MoveError::PopNotAllowed);
+        }
+
+        if self.status() != BoardStatus::OnGoing {
+            return Err(MoveError::ConcludedGame);
+        }
+
+        if !self.can_pop(column) {
+            return Err(MoveError::NotOwnDisc);
+        }
+
+        let column_mask = self.size.column_mask(column);
+        let bottom = column_mask & self.size.bottom_mask();
+        let shift_down =
+            |bitboard: u128| (bitboard & !column_mask) | ((bitboard & column_mask & !bottom) >> 1);
+        self.player_one_bitboard = shift_down(self.player_one_bitboard);
+        self.player_two_bitboard = shift_down(self.player_two_bitboard);
+
+        self.heights[column.to_index()] -= 1;
+        self.finish_move(Move::Pop(column));
+
+        Ok(bottom)
+    }
+
+    /// Plays either kind of move.
+    pub fn try_play(&mut self, next_move: Move) -> Result<u128, MoveError> {
+        match next_move {
+            Move::Drop(column) => self.try_move(column),
+            Move::Pop(column) => self.try_pop(column),
         }
     }
 
+    fn finish_move(&mut self, next_move: Move) {
+        self.move_count += 1;
+        self.history.push(next_move);
+        if self.variant() == Variant::PopOut {
+            self.positions.push(self.position_key());
+        }
+    }
+
+    /// Takes back the last move, returning it.
+    pub fn pop_move(&mut self) -> Option<Move> {
+        let last_move = self.history.pop()?;
+        let idx = last_move.column().to_index();
+        self.move_count -= 1;
+        self.positions.pop();
+
+        match last_move {
+            Move::Drop(_) => {
+                self.heights[idx] -= 1;
+                let old_position = 1 << self.heights[idx];
+                match self.current_player() {
+                    Player::One => self.player_one_bitboard ^= old_position,
+                    Player::Two => self.player_two_bitboard ^= old_position,
+                };
+            }
+            Move::Pop(column) => {
+                self.heights[idx] += 1;
+                let column_mask = self.size.column_mask(column);
+                let bottom = column_mask & self.size.bottom_mask();
+                let shift_up =
+                    |bitboard: u128| (bitboard & !column_mask) | ((bitboard & column_mask) << 1);
+                self.player_one_bitboard = shift_up(self.player_one_bitboard);
+                self.player_two_bitboard = shift_up(self.player_two_bitboard);
+                match self.current_player() {
+                    Player::One => self.player_one_bitboard |= bottom,
+                    Player::Two => self.player_two_bitboard |= bottom,
+                };
+            }
+        }
+        Some(last_move)
+    }
+
     pub fn is_playable(&self, column: Column) -> bool {
-        let idx = column.to_index();
-        self.heights[idx] < TOP[idx]
+        self.size.contains(column) && self.column_height(column) < self.size.height
+    }
+
+    /// Whether the player to move may pop their disc out of the bottom of `column`.
+    pub fn can_pop(&self, column: Column) -> bool {
+        self.variant() == Variant::PopOut
+            && self.size.contains(column)
+            && self.current_bitboard() & self.size.column_mask(column) & self.size.bottom_mask()
+                != 0
+    }
+
+    /// Whether `next_move` is a drop into a column that is not full, or a pop the player to
+    /// move is allowed to make.
+    pub fn is_legal(&self, next_move: Move) -> bool {
+        match next_move {
+            Move::Drop(column) => self.is_playable(column),
+            Move::Pop(column) => self.can_pop(column),
+        }
+    }
+
+    /// Every move the player to move can make, drops before pops and each from the first
+    /// column to the last.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.status() != BoardStatus::OnGoing {
+            return Vec::new();
+        }
+        let drops = self.size.columns().map(Move::Drop);
+        let pops = self.size.columns().map(Move::Pop);
+        drops
+            .chain(pops)
+            .filter(|&next_move| self.is_legal(next_move))
+            .collect()
     }
 
-    pub fn player_one_bitboard(&self) -> u64 {
+    /// Most moves the game can still last, which is unlimited in PopOut games where
+    /// popping discs makes room for more.
+    pub fn moves_left(&self) -> Option<u8> {
+        match self.variant() {
+            Variant::Standard => {
+                let discs = (self.player_one_bitboard | self.player_two_bitboard).count_ones();
+                Some(self.size.slots() - discs as u8)
+            }
+            Variant::PopOut => None,
+        }
+    }
+
+    /// Slots a disc would land in for every column that is not full.
+    pub fn playable_moves(&self) -> u128 {
+        if self.status() != BoardStatus::OnGoing {
+            return 0;
+        }
+        let mask = self.player_one_bitboard | self.player_two_bitboard;
+        (mask + self.size.bottom_mask()) & self.size.board_mask()
+    }
+
+    /// Playable slots that would complete a line for `player`, whether or not it is their turn.
+    pub fn winning_moves(&self, player: Player) -> u128 {
+        let mask = self.player_one_bitboard | self.player_two_bitboard;
+        let discs = match player {
+            Player::One => self.player_one_bitboard,
+            Player::Two => self.player_two_bitboard,
+        };
+        winning_position(discs, mask, self.size, self.connect()) & self.playable_moves()
+    }
+
+    pub fn winning_columns(&self, player: Player) -> Vec<Column> {
+        self.to_columns(self.winning_moves(player))
+    }
+
+    /// Playable slots for the player to move that do not hand the opponent a win on their
+    /// next move. A threat of the opponent has to be blocked, and moving right below one
+    /// lets the opponent play it. Empty when every move loses, such as against two threats.
+    pub fn non_losing_moves(&self) -> u128 {
+        let mut playable = self.playable_moves();
+        let opponent = self.current_player().opponent();
+        let mask = self.player_one_bitboard | self.player_two_bitboard;
+        let opponent_threats = match opponent {
+            Player::One => {
+                winning_position(self.player_one_bitboard, mask, self.size, self.connect())
+            }
+            Player::Two => {
+                winning_position(self.player_two_bitboard, mask, self.size, self.connect())
+            }
+        };
+        let forced = playable & opponent_threats;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                return 0;
+            }
+            playable = forced;
+        }
+        playable & !(opponent_threats >> 1)
+    }
+
+    pub fn non_losing_columns(&self) -> Vec<Column> {
+        self.to_columns(self.non_losing_moves())
+    }
+
+    /// Columns of the slots set in `moves`, which hold at most one slot per column.
+    fn to_columns(&self, moves: u128) -> Vec<Column> {
+        self.size
+            .columns()
+            .filter(|&column| moves & self.size.column_mask(column) != 0)
+            .collect()
+    }
+
+    pub fn player_one_bitboard(&self) -> u128 {
         self.player_one_bitboard
     }
 
-    pub fn player_two_bitboard(&self) -> u64 {
+    pub fn player_two_bitboard(&self) -> u128 {
         self.player_two_bitboard
     }
 
     pub fn column_height(&self, column: Column) -> u8 {
         let idx = column.to_index();
-        self.heights[idx] % 7
+        self.heights[idx] - idx as u8 * self.size.stride() as u8
+    }
+
+    pub fn move_count(&self) -> u16 {
+        self.move_count
+    }
+
+    fn current_bitboard(&self) -> u128 {
+        match self.current_player() {
+            Player::One => self.player_one_bitboard,
+            Player::Two => self.player_two_bitboard,
+        }
+    }
+
+    /// Key of the position regardless of the order the moves were made in.
+    /// Adding the occupied slots to the current player's discs sets a single bit
+    /// above each column's height, which makes the sum unambiguous. The rules are packed in
+    /// as described by `pack_key`, so tables can be shared between them.
+    pub fn key(&self) -> u64 {
+        pack_key(self.position_key(), self.size, self.rules)
+    }
+
+    /// Key shared by the position and its mirror image, which score the same. Use it for
+    /// tables that should only hold one of the two.
+    pub fn canonical_key(&self) -> u64 {
+        let key = self.position_key();
+        pack_key(key.min(mirror(key, self.size)), self.size, self.rules)
+    }
+
+    fn position_key(&self) -> u128 {
+        self.current_bitboard() + (self.player_one_bitboard | self.player_two_bitboard)
+    }
+
+    /// The board flipped left to right, as if every move had been made in the opposite column.
+    pub fn mirror(&self) -> Self {
+        let width = self.size.width();
+        let stride = self.size.stride() as u8;
+        let mirror_column = |column: Column| {
+            Column::try_from(width + 1 - column.to_u8()).expect("mirrored column is on the board")
+        };
+        let mut heights = self.heights;
+        for col in 0..width {
+            let mirrored = width - 1 - col;
+            heights[mirrored as usize] =
+                mirrored * stride + self.heights[col as usize] - col * stride;
+        }
+        Self {
+            player_one_bitboard: mirror(self.player_one_bitboard, self.size),
+            player_two_bitboard: mirror(self.player_two_bitboard, self.size),
+            heights,
+            history: self
+                .history
+                .iter()
+                .map(|&mv| match mv {
+                    Move::Drop(column) => Move::Drop(mirror_column(column)),
+                    Move::Pop(column) => Move::Pop(mirror_column(column)),
+                })
+                .collect(),
+            positions: self
+                .positions
+                .iter()
+                .map(|&position| mirror(position, self.size))
+                .collect(),
+            ..*self
+        }
     }
 
     pub fn current_player(&self) -> Player {
         Player::from_move_count(self.move_count)
     }
 
-    pub fn status(&self) -> BoardStatus {
-        if has_winner(self.player_one_bitboard) {
-            return BoardStatus::Winner(Player::One);
+    /// Discs of the winner that make up a winning line, with every line included when a
+    /// move completed several at once. Empty while nobody has won.
+    pub fn winning_line(&self) -> u128 {
+        let discs = match self.status() {
+            BoardStatus::Winner(Player::One) => self.player_one_bitboard,
+            BoardStatus::Winner(Player::Two) => self.player_two_bitboard,
+            _ => return 0,
+        };
+        let stride = self.size.stride();
+        let mut line = 0;
+        for shift in [1, stride, stride - 1, stride + 1] {
+            let starts = line_starts(discs, shift, self.connect());
+            for disc in 0..self.connect() as u32 {
+                line |= starts << (disc * shift);
+            }
         }
-        if has_winner(self.player_two_bitboard) {
-            return BoardStatus::Winner(Player::Two);
+        line
+    }
+
+    pub fn status(&self) -> BoardStatus {
+        match (
+            has_winner(self.player_one_bitboard, self.size, self.connect()),
+            has_winner(self.player_two_bitboard, self.size, self.connect()),
+        ) {
+            // Only pops can complete lines for both players, which wins for the one who popped.
+            (true, true) => return BoardStatus::Winner(self.current_player().opponent()),
+            (true, false) => return BoardStatus::Winner(Player::One),
+            (false, true) => return BoardStatus::Winner(Player::Two),
+            (false, false) => {}
         }
-        if self.move_count == 42 {
-            return BoardStatus::Draw;
+
+        let full = self.player_one_bitboard | self.player_two_bitboard == self.size.board_mask();
+        match self.variant() {
+            Variant::Standard if full => BoardStatus::Draw,
+            Variant::PopOut
+                if (full && self.current_bitboard() & self.size.bottom_mask() == 0)
+                    || self.is_third_repetition() =>
+            {
+                BoardStatus::Draw
+            }
+            _ => BoardStatus::OnGoing,
         }
-        BoardStatus::OnGoing
+    }
+
+    // Positions two moves apart have the same player to move, so only those can repeat.
+    fn is_third_repetition(&self) -> bool {
+        let Some(&last) = self.positions.last() else {
+            return false;
+        };
+        self.positions
+            .iter()
+            .rev()
+            .step_by(2)
+            .filter(|&&key| key == last)
+            .count()
+            >= 3
+    }
+}
+
+impl FromStr for ConnectFourBoard {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ConnectFourBoard::from_moves(s)
+    }
+}
+
+impl fmt::Display for ConnectFourBoard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_moves())
     }
 }
 
 impl Default for ConnectFourBoard {
     fn default() -> Self {
-        Self {
-            player_one_bitboard: 0,
-            player_two_bitboard: 0,
-            move_count: 0,
-            heights: [0, 7, 14, 21, 28, 35, 42],
-            history: Vec::with_capacity(42),
+        Self::new(BoardSize::STANDARD)
+    }
+}
+
+/// Integer a bitboard is stored in. Every board fits in a `u128`, while boards whose columns
+/// take up at most 64 bits also fit in a `u64`, which is quicker to work with.
+pub(crate) trait Bitboard:
+    Copy
+    + Default
+    + Ord
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + BitAnd<Output = Self>
+    + BitAndAssign
+    + BitOr<Output = Self>
+    + BitOrAssign
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    const EMPTY: Self;
+    const FULL: Self;
+
+    /// Keeps the bits of a `u128` bitboard that fit.
+    fn truncate(bitboard: u128) -> Self;
+
+    fn widen(self) -> u128;
+
+    fn count_ones(self) -> u32;
+}
+
+impl Bitboard for u64 {
+    const EMPTY: Self = 0;
+    const FULL: Self = u64::MAX;
+
+    fn truncate(bitboard: u128) -> Self {
+        bitboard as u64
+    }
+
+    fn widen(self) -> u128 {
+        self as u128
+    }
+
+    fn count_ones(self) -> u32 {
+        self.count_ones()
+    }
+}
+
+impl Bitboard for u128 {
+    const EMPTY: Self = 0;
+    const FULL: Self = u128::MAX;
+
+    fn truncate(bitboard: u128) -> Self {
+        bitboard
+    }
+
+    fn widen(self) -> u128 {
+        self
+    }
+
+    fn count_ones(self) -> u32 {
+        self.count_ones()
+    }
+}
+
+/// Packs a position key, where every column holds its discs and a bit on top, into 64 bits
+/// along with the rules. The rules take the three bits above the last column, so keys are
+/// unique on boards whose columns take up at most 61 bits, which leaves out only 9x6, 8x7,
+/// 7x8 and larger boards. Keys of those are folded into 64 bits and can collide.
+pub(crate) fn pack_key(key: u128, size: BoardSize, rules: Rules) -> u64 {
+    let popout = (rules.variant() == Variant::PopOut) as u128;
+    let rules = ((rules.connect() - MIN_CONNECT) as u128) << 1 | popout;
+    let key = key | rules << size.bits();
+    key as u64 ^ ((key >> 64) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Empty slots that would complete a line of `connect` for the discs in `position`,
+/// where `mask` holds the discs of both players.
+pub(crate) fn winning_position<B: Bitboard>(
+    position: B,
+    mask: B,
+    size: BoardSize,
+    connect: u8,
+) -> B {
+    line_completions(position, size.stride(), connect) & (B::truncate(size.board_mask()) ^ mask)
+}
+
+/// Slots that complete a line of `connect` for the discs in `position`, on or off the board
+/// and whether or not they are taken.
+pub(crate) fn line_completions<B: Bitboard>(position: B, stride: u32, connect: u8) -> B {
+    // The search calls this in its hottest loops, so every length gets its own unrolled copy.
+    match connect {
+        3 => line_ends::<B, 3>(position, stride),
+        4 => line_ends::<B, 4>(position, stride),
+        _ => line_ends::<B, 5>(position, stride),
+    }
+}
+
+/// Slots that complete a line of `N` for the discs in `position`, on or off the board.
+fn line_ends<B: Bitboard, const N: usize>(position: B, stride: u32) -> B {
+    // Vertical lines can only be completed on top.
+    let mut r = B::FULL;
+    for disc in 1..N {
+        r &= position << disc as u32;
+    }
+
+    // Every other line can be completed on any of its slots, by runs of discs on either side
+    // that add up to one short of the line.
+    for shift in [stride, stride - 1, stride + 1] {
+        let (mut below, mut above) = ([B::FULL; N], [B::FULL; N]);
+        for disc in 1..N {
+            below[disc] = below[disc - 1] & (position << (disc as u32 * shift));
+            above[disc] = above[disc - 1] & (position >> (disc as u32 * shift));
+        }
+        for disc in 0..N {
+            r |= below[disc] & above[N - 1 - disc];
         }
     }
+    r
 }
 
-fn has_winner(bitboard: u64) -> bool {
-    let horizontal = bitboard & (bitboard >> 7);
-    let vertical = bitboard & (bitboard >> 1);
-    let diag_one = bitboard & (bitboard >> 6);
-    let diag_two = bitboard & (bitboard >> 8);
-    (diag_one & (diag_one >> (2 * 6)))
-        | (horizontal & (horizontal >> (2 * 7)))
-        | (diag_two & (diag_two >> (2 * 8)))
-        | (vertical & (vertical >> 2))
-        != 0
+/// Flips a bitboard horizontally. Columns keep their own bits, so sums of bitboards such as
+/// position keys can be flipped as well.
+pub(crate) fn mirror<B: Bitboard>(bitboard: B, size: BoardSize) -> B {
+    let stride = size.stride();
+    let column = B::truncate((1 << stride) - 1);
+    let width = size.width() as u32;
+    let mut mirrored = B::EMPTY;
+    for col in 0..width {
+        let bits = (bitboard >> (col * stride)) & column;
+        mirrored |= bits << ((width - 1 - col) * stride);
+    }
+    mirrored
+}
+
+/// Discs in `bitboard` that start a line of `connect` running in steps of `shift` bits.
+fn line_starts(bitboard: u128, shift: u32, connect: u8) -> u128 {
+    // Doubling the length covered each step takes fewer shifts than one disc at a time.
+    let mut starts = bitboard;
+    let mut length = 1;
+    while length * 2 <= connect as u32 {
+        starts &= starts >> (length * shift);
+        length *= 2;
+    }
+    if length < connect as u32 {
+        starts &= starts >> ((connect as u32 - length) * shift);
+    }
+    starts
+}
+
+fn has_winner(bitboard: u128, size: BoardSize, connect: u8) -> bool {
+    let stride = size.stride();
+    [1, stride, stride - 1, stride + 1]
+        .into_iter()
+        .any(|shift| line_starts(bitboard, shift, connect) != 0)
 }
 
 #[derive(Debug, PartialEq)]
@@ -240,8 +1047,9 @@ pub enum Slot {
     Vacant,
 }
 
+/// Slots of a board column by column, each from the bottom up.
 pub struct BoardSlots<'a> {
-    cursor: u64,
+    cursor: u32,
     board: &'a ConnectFourBoard,
 }
 
@@ -255,11 +1063,12 @@ impl Iterator for BoardSlots<'_> {
     type Item = Slot;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if (1 << self.cursor) & TOP_BITBOARD != 0 {
+        let size = self.board.size();
+        if self.cursor % size.stride() == size.height() as u32 {
             self.cursor += 1;
         }
 
-        if self.cursor >= 48 {
+        if self.cursor >= size.width() as u32 * size.stride() {
             return None;
         }
 
@@ -275,9 +1084,24 @@ impl Iterator for BoardSlots<'_> {
     }
 }
 
+/// Positions the tests of several modules play from.
+#[cfg(test)]
+pub(crate) mod test_positions {
+    use super::ConnectFourBoard;
+
+    /// Player one can win in the first column, while player two has two threats that can not
+    /// both be blocked, so every other column loses.
+    pub(crate) fn double_threat() -> ConnectFourBoard {
+        ConnectFourBoard::from_moves("121212535363").unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::game::{BoardStatus, MoveError, Player, Slot};
+    use crate::game::{
+        BoardSize, BoardSizeError, BoardStatus, ConnectOutOfRange, MAX_CONNECT, MIN_CONNECT, Move,
+        MoveError, ParseError, Player, Rules, Slot, UnknownVariant, Variant,
+    };
 
     use super::{Column, ConnectFourBoard, has_winner};
 
@@ -339,6 +1163,56 @@ mod tests {
         assert_eq!(Err(MoveError::ConcludedGame), board.try_move(Column::One));
     }
 
+    #[test]
+    fn parses_move_sequence() {
+        let board = ConnectFourBoard::from_moves("4453").unwrap();
+        let mut expected = ConnectFourBoard::default();
+        for column in [Column::Four, Column::Four, Column::Five, Column::Three] {
+            let _ = expected.try_move(column);
+        }
+
+        assert_eq!(expected.player_one_bitboard(), board.player_one_bitboard());
+        assert_eq!(expected.player_two_bitboard(), board.player_two_bitboard());
+        assert_eq!("4453", board.to_moves());
+        assert_eq!("4453", board.to_string());
+    }
+
+    #[test]
+    fn round_trips_move_sequence() {
+        let board: ConnectFourBoard = "1234567".parse().unwrap();
+        let board: ConnectFourBoard = board.to_string().parse().unwrap();
+        assert_eq!("1234567", board.to_moves());
+        assert_eq!("", ConnectFourBoard::default().to_moves());
+    }
+
+    #[test]
+    fn error_parsing_invalid_column() {
+        assert_eq!(
+            Some(ParseError::InvalidColumn('8')),
+            ConnectFourBoard::from_moves("448").err()
+        );
+        assert_eq!(
+            Some(ParseError::InvalidColumn('x')),
+            ConnectFourBoard::from_moves("4x").err()
+        );
+    }
+
+    #[test]
+    fn error_parsing_full_column() {
+        assert_eq!(
+            Some(ParseError::FullColumn(Column::One)),
+            ConnectFourBoard::from_moves("1111111").err()
+        );
+    }
+
+    #[test]
+    fn error_parsing_moves_after_concluded_game() {
+        assert_eq!(
+            Some(ParseError::ConcludedGame(Column::Three)),
+            ConnectFourBoard::from_moves("12121213").err()
+        );
+    }
+
     #[test]
     fn players_alternate_between_moves() {
         let mut board = ConnectFourBoard::default();
@@ -354,29 +1228,118 @@ mod tests {
         assert_ne!(0, board.player_two_bitboard());
     }
 
+    #[test]
+    fn pops_last_move() {
+        let mut board = ConnectFourBoard::default();
+        let _ = board.try_move(Column::One);
+        let _ = board.try_move(Column::Two);
+
+        assert_eq!(Some(Move::Drop(Column::Two)), board.pop_move());
+        assert_eq!(Player::Two, board.current_player());
+        assert_eq!(0, board.player_two_bitboard());
+        assert_eq!(0, board.column_height(Column::Two));
+
+        assert_eq!(Some(Move::Drop(Column::One)), board.pop_move());
+        assert_eq!(None, board.pop_move());
+        assert_eq!(0, board.player_one_bitboard());
+    }
+
+    #[test]
+    fn key_is_independent_of_move_order() {
+        let mut first = ConnectFourBoard::default();
+        for column in [Column::One, Column::Two, Column::Three, Column::Four] {
+            let _ = first.try_move(column);
+        }
+        let mut second = ConnectFourBoard::default();
+        for column in [Column::Three, Column::Four, Column::One, Column::Two] {
+            let _ = second.try_move(column);
+        }
+        let mut third = ConnectFourBoard::default();
+        for column in [Column::Two, Column::One, Column::Four, Column::Three] {
+            let _ = third.try_move(column);
+        }
+
+        assert_eq!(first.key(), second.key());
+        assert_ne!(first.key(), third.key());
+    }
+
+    #[test]
+    fn mirrored_positions_share_canonical_key() {
+        let board = ConnectFourBoard::from_moves("1123").unwrap();
+        let mirrored = board.mirror();
+        assert_eq!("7765", mirrored.to_moves());
+        assert_eq!(
+            ConnectFourBoard::from_moves("7765").unwrap().key(),
+            mirrored.key()
+        );
+        assert_ne!(board.key(), mirrored.key());
+        assert_eq!(board.canonical_key(), mirrored.canonical_key());
+        assert_eq!(board.key(), mirrored.mirror().key());
+
+        // Symmetric positions are their own mirror image.
+        let symmetric = ConnectFourBoard::from_moves("4444").unwrap();
+        assert_eq!(symmetric.key(), symmetric.mirror().key());
+        assert_eq!(symmetric.key(), symmetric.canonical_key());
+
+        let other = ConnectFourBoard::from_moves("1132").unwrap();
+        assert_ne!(board.canonical_key(), other.canonical_key());
+    }
+
+    #[test]
+    fn mirrored_board_keeps_playing() {
+        let size = BoardSize::new(8, 7).unwrap();
+        let mut mirrored = ConnectFourBoard::from_moves_with_size("121213", size)
+            .unwrap()
+            .mirror();
+        assert_eq!(Player::One, mirrored.current_player());
+        assert!(mirrored.try_move(Column::Eight).is_ok());
+        assert_eq!(BoardStatus::Winner(Player::One), mirrored.status());
+        assert_eq!(Some(Move::Drop(Column::Eight)), mirrored.pop_move());
+
+        let mut board = popout("4455p4");
+        let mut mirrored = board.mirror();
+        assert_eq!("4433p4", mirrored.to_moves());
+        assert_eq!(board.canonical_key(), mirrored.canonical_key());
+        board.play_moves("4").unwrap();
+        mirrored.play_moves("4").unwrap();
+        assert_eq!(board.canonical_key(), mirrored.canonical_key());
+    }
+
     #[test]
     fn determines_when_winner_exists() {
         assert!(has_winner(
-            0b1111000_0000000_0000000_0000000_0000000_0000000_0000000
+            0b1111000_0000000_0000000_0000000_0000000_0000000_0000000,
+            BoardSize::STANDARD,
+            4
         ));
         assert!(has_winner(
-            0b1000000_1000000_1000000_1000000_0000000_0000000_0000000
+            0b1000000_1000000_1000000_1000000_0000000_0000000_0000000,
+            BoardSize::STANDARD,
+            4
         ));
         assert!(has_winner(
-            0b1000000_0100000_0010000_0001000_0000000_0000000_0000000
+            0b1000000_0100000_0010000_0001000_0000000_0000000_0000000,
+            BoardSize::STANDARD,
+            4
         ));
         assert!(has_winner(
-            0b0001000_0010000_0100000_1000000_0000000_0000000_0000000
+            0b0001000_0010000_0100000_1000000_0000000_0000000_0000000,
+            BoardSize::STANDARD,
+            4
         ));
     }
 
     #[test]
     fn determines_when_no_winner_exists() {
         assert!(!has_winner(
-            0b1110000_0000000_0000000_0000000_0000000_0000000_0000000
+            0b1110000_0000000_0000000_0000000_0000000_0000000_0000000,
+            BoardSize::STANDARD,
+            4
         ));
         assert!(!has_winner(
-            0b1110110_1110000_0000000_0001000_0000000_1100000_1000000
+            0b1110110_1110000_0000000_0001000_0000000_1100000_1000000,
+            BoardSize::STANDARD,
+            4
         ));
     }
 
@@ -436,4 +1399,239 @@ mod tests {
         ];
         assert_eq!(expected_slots, slots);
     }
+
+    #[test]
+    fn finds_winning_moves() {
+        // Both players have three stacked discs, and player two also has three in column 3.
+        let board = ConnectFourBoard::from_moves("121212535363").unwrap();
+        assert_eq!(vec![Column::One], board.winning_columns(Player::One));
+        assert_eq!(
+            vec![Column::Two, Column::Three],
+            board.winning_columns(Player::Two)
+        );
+        assert_eq!(1 << 3, board.winning_moves(Player::One));
+        assert_eq!(0, board.non_losing_moves());
+
+        let board = ConnectFourBoard::from_moves("1212121").unwrap();
+        assert_eq!(0, board.winning_moves(Player::Two));
+    }
+
+    #[test]
+    fn forces_block_of_single_threat() {
+        let board = ConnectFourBoard::from_moves("11223").unwrap();
+        assert_eq!(vec![Column::Four], board.non_losing_columns());
+
+        // Playing below a threat of player one would let them take it.
+        let board = ConnectFourBoard::from_moves("112233").unwrap();
+        assert_eq!(vec![Column::Four], board.winning_columns(Player::One));
+        let board = ConnectFourBoard::from_moves("3112273").unwrap();
+        assert!(!board.non_losing_columns().contains(&Column::Four));
+        assert_eq!(6, board.non_losing_columns().len());
+    }
+
+    #[test]
+    fn finds_winning_line() {
+        assert_eq!(
+            0,
+            ConnectFourBoard::from_moves("4455").unwrap().winning_line()
+        );
+
+        let board = ConnectFourBoard::from_moves("1212121").unwrap();
+        assert_eq!(0b1111, board.winning_line());
+
+        // The disc in the corner of the L completes a row and a column at once.
+        let row = (1 << 0) | (1 << 7) | (1 << 14) | (1 << 21);
+        let column = 0b1111 << 21;
+        let board = ConnectFourBoard {
+            player_one_bitboard: row | column | (1 << 30),
+            ..Default::default()
+        };
+        assert_eq!(row | column, board.winning_line());
+    }
+
+    #[test]
+    fn parses_board_sizes() {
+        assert_eq!(Ok(BoardSize::STANDARD), "7x6".parse());
+        assert_eq!("9x8", "9x8".parse::<BoardSize>().unwrap().to_string());
+        assert_eq!(
+            Err(BoardSizeError::OutOfRange(10, 6)),
+            "10x6".parse::<BoardSize>()
+        );
+        assert_eq!(
+            Err(BoardSizeError::InvalidFormat("7 by 6".into())),
+            "7 by 6".parse::<BoardSize>()
+        );
+    }
+
+    #[test]
+    fn plays_on_larger_board() {
+        let size = BoardSize::new(9, 8).unwrap();
+        let mut board = ConnectFourBoard::new(size);
+        assert_eq!(72, board.slots().count());
+        for _ in 0..8 {
+            assert!(board.try_move(Column::Nine).is_ok());
+        }
+        assert_eq!(Err(MoveError::FullColumn), board.try_move(Column::Nine));
+        assert_eq!(
+            Err(MoveError::OffBoard),
+            ConnectFourBoard::default().try_move(Column::Eight)
+        );
+
+        let board = ConnectFourBoard::from_moves_with_size("6171819", size).unwrap();
+        assert_eq!(BoardStatus::Winner(Player::One), board.status());
+    }
+
+    #[test]
+    fn keeps_keys_of_other_rules_apart() {
+        // A full last column reaches the bits right below the rules.
+        for (width, height) in [(7, 6), (8, 6), (8, 7), (9, 6), (9, 8)] {
+            let size = BoardSize::new(width, height).unwrap();
+            let mut keys = Vec::new();
+            for variant in [Variant::Standard, Variant::PopOut] {
+                for length in MIN_CONNECT..=MAX_CONNECT {
+                    let rules = Rules::new(variant, length).unwrap();
+                    let mut board = ConnectFourBoard::with_rules(size, rules);
+                    for _ in 0..height {
+                        board.try_move(Column::try_from(width).unwrap()).unwrap();
+                    }
+                    keys.push(board.key());
+                }
+            }
+            keys.sort_unstable();
+            keys.dedup();
+            assert_eq!(6, keys.len(), "{size}");
+        }
+    }
+
+    fn connect(length: u8, moves: &str) -> ConnectFourBoard {
+        let rules = Rules::new(Variant::Standard, length).unwrap();
+        let mut board = ConnectFourBoard::with_rules(BoardSize::STANDARD, rules);
+        board.play_moves(moves).unwrap();
+        board
+    }
+
+    #[test]
+    fn wins_with_lines_of_three() {
+        assert_eq!(
+            BoardStatus::Winner(Player::One),
+            connect(3, "14243").status()
+        );
+        assert_eq!(
+            BoardStatus::Winner(Player::One),
+            connect(3, "1223433").status()
+        );
+        let board = connect(3, "1424");
+        assert_eq!(BoardStatus::OnGoing, board.status());
+        assert_eq!(vec![Column::Three], board.winning_columns(Player::One));
+        assert_eq!(1 | 1 << 7 | 1 << 14, connect(3, "14243").winning_line());
+    }
+
+    #[test]
+    fn wins_with_lines_of_five() {
+        assert_eq!(BoardStatus::OnGoing, connect(5, "1121314").status());
+        let board = connect(5, "112131415");
+        assert_eq!(BoardStatus::Winner(Player::One), board.status());
+        assert_eq!(
+            (0..5).map(|col| 1 << (col * 7)).sum::<u128>(),
+            board.winning_line()
+        );
+        // Four on a diagonal only wins with the default rules.
+        let moves = "12233434474";
+        assert_eq!(BoardStatus::OnGoing, connect(5, moves).status());
+        assert_eq!(
+            BoardStatus::Winner(Player::One),
+            ConnectFourBoard::from_moves(moves).unwrap().status()
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_lines() {
+        assert_eq!(Err(ConnectOutOfRange(2)), Rules::new(Variant::Standard, 2));
+        assert_eq!(Err(ConnectOutOfRange(6)), Rules::new(Variant::PopOut, 6));
+        assert_ne!(connect(5, "44").key(), connect(4, "44").key());
+    }
+
+    fn popout(moves: &str) -> ConnectFourBoard {
+        let mut board = ConnectFourBoard::with_rules(
+            BoardSize::STANDARD,
+            Rules::new(Variant::PopOut, 4).unwrap(),
+        );
+        board.play_moves(moves).unwrap();
+        board
+    }
+
+    #[test]
+    fn pops_own_disc_out_of_column() {
+        let mut board = popout("4455");
+        assert!(board.can_pop(Column::Four));
+        assert!(!board.can_pop(Column::Six));
+        assert_eq!(Ok(1 << 21), board.try_pop(Column::Four));
+        // Player two's disc dropped to the bottom of the column.
+        assert_eq!(1 << 21, board.player_two_bitboard() & (0b111111 << 21));
+        assert_eq!(0, board.player_one_bitboard() & (0b111111 << 21));
+        assert_eq!(1, board.column_height(Column::Four));
+        assert_eq!(Player::Two, board.current_player());
+        assert_eq!("4455p4", board.to_moves());
+
+        assert_eq!(Some(Move::Pop(Column::Four)), board.pop_move());
+        let expected = popout("4455");
+        assert_eq!(expected.player_one_bitboard(), board.player_one_bitboard());
+        assert_eq!(expected.player_two_bitboard(), board.player_two_bitboard());
+        assert_eq!(expected.key(), board.key());
+    }
+
+    #[test]
+    fn rejects_invalid_pops() {
+        let mut board = popout("45");
+        assert_eq!(Err(MoveError::NotOwnDisc), board.try_pop(Column::Five));
+        assert_eq!(Err(MoveError::NotOwnDisc), board.try_pop(Column::One));
+
+        let mut board = ConnectFourBoard::from_moves("45").unwrap();
+        assert_eq!(Err(MoveError::PopNotAllowed), board.try_pop(Column::Four));
+        assert_eq!(
+            Err(ParseError::InvalidColumn('p')),
+            ConnectFourBoard::from_moves("4p4").map(|_| ())
+        );
+        assert_eq!(
+            Err(ParseError::InvalidPop(Column::Five)),
+            popout("").play_moves("45p5")
+        );
+    }
+
+    #[test]
+    fn lists_legal_moves() {
+        let board = popout("4455");
+        assert_eq!(9, board.legal_moves().len());
+        assert_eq!(Some(&Move::Pop(Column::Five)), board.legal_moves().last());
+        assert_eq!(None, board.moves_left());
+        assert_eq!(
+            Some(38),
+            ConnectFourBoard::from_moves("4455").unwrap().moves_left()
+        );
+    }
+
+    #[test]
+    fn pop_connecting_four_for_both_players_wins_for_popper() {
+        // Popping the first column drops player one's disc next to their row of three and
+        // player two's disc next to theirs.
+        let board = popout("1112233447p1");
+        assert_eq!(BoardStatus::Winner(Player::One), board.status());
+    }
+
+    #[test]
+    fn third_repetition_is_a_draw() {
+        // Both players pop their only disc, which empties the board again.
+        assert_eq!(BoardStatus::OnGoing, popout("12p1p2").status());
+        assert_eq!(BoardStatus::Draw, popout("12p1p212p1p2").status());
+    }
+
+    #[test]
+    fn parses_moves_and_variants() {
+        assert_eq!(Some(Move::Pop(Column::Three)), "p3".parse().ok());
+        assert_eq!(Some(Move::Drop(Column::Three)), "3".parse().ok());
+        assert!("p".parse::<Move>().is_err());
+        assert_eq!("p7", Move::Pop(Column::Seven).to_string());
+        assert_eq!(Ok(Variant::PopOut), "popout".parse());
+        assert_eq!(Err(UnknownVariant("pop".into())), "pop".parse::<Variant>());
+    }
 }
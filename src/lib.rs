@@ -0,0 +1,5 @@
+pub mod ai;
+pub mod engine;
+pub mod game;
+pub mod net;
+pub mod tournament;
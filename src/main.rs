@@ -1,14 +1,65 @@
-use app::App;
+use std::{io, sync::Arc};
+
+use app::{App, Opponent};
+use cli::{Cli, Command, USAGE};
+use connect_fors::{
+    ai::{Book, BookError},
+    engine::ExternalEngine,
+};
 
-mod ai;
 mod app;
+mod cli;
 mod event;
-mod game;
+mod save;
 mod ui;
 
 fn main() -> anyhow::Result<()> {
-    let terminal = ratatui::init();
-    let app_result = App::new().run(terminal);
-    ratatui::restore();
-    app_result
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprint!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Command::Play {
+        engine,
+        size,
+        rules,
+        threads,
+    } = &cli.command
+    {
+        // The engine is started up front so a bad command fails before the terminal UI opens.
+        let opponent = match engine {
+            Some(command) => {
+                let mut words = command.split_whitespace();
+                let program = words.next().unwrap_or_default();
+                Opponent::Engine(Arc::new(ExternalEngine::spawn(program, words)?))
+            }
+            None => Opponent::Ai,
+        };
+        install_book(&cli)?;
+        let terminal = ratatui::init();
+        let app_result = App::new(opponent, *size, *rules, *threads).run(terminal);
+        ratatui::restore();
+        return app_result;
+    }
+    cli.run(&mut std::io::stdout())
+}
+
+/// Lets the AI play the opening from the book, if one has been built.
+fn install_book(cli: &Cli) -> anyhow::Result<()> {
+    let Ok(path) = cli.book_path() else {
+        return Ok(());
+    };
+    match Book::load(path) {
+        Ok(book) => {
+            let _ = book.install();
+            Ok(())
+        }
+        // Without a book, the AI searches from the first move.
+        Err(BookError::Io(err)) if cli.book.is_none() && err.kind() == io::ErrorKind::NotFound => {
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
 }
@@ -0,0 +1,579 @@
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::game::{BoardSize, BoardStatus, ConnectFourBoard, Move, Player, Rules, Variant};
+
+/// Version of the line protocol, exchanged by both sides when connecting.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+pub const DEFAULT_PORT: u16 = 7777;
+
+const ACCEPT_POLL_RATE: Duration = Duration::from_millis(100);
+
+#[derive(Error, Debug)]
+pub enum NetError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("Received an unrecognized message: {0}")]
+    InvalidMessage(String),
+
+    #[error("Peer speaks protocol version {0}, expected version {PROTOCOL_VERSION}.")]
+    VersionMismatch(u32),
+
+    #[error("Peer disconnected.")]
+    Disconnected,
+
+    #[error("Stopped waiting for a peer to connect.")]
+    Cancelled,
+
+    #[error("Move {0} is not legal.")]
+    IllegalMove(Move),
+
+    #[error("It is not your turn.")]
+    NotYourTurn,
+
+    #[error("A rematch can only start once the game is over.")]
+    GameNotOver,
+
+    #[error("The rules can only be set before the first move.")]
+    GameStarted,
+}
+
+/// A single line of the protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Hello(u32),
+    /// Board size and rules of the game, sent by the host before the first move.
+    Rules(BoardSize, Rules),
+    Move(Move),
+    Rematch,
+    /// Refusal of a move received from the peer, along with the reason.
+    Reject(Move, String),
+    Error(String),
+    Bye,
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Hello(version) => write!(f, "HELLO {}", version),
+            Message::Rules(size, rules) => {
+                write!(f, "RULES {} {} {}", size, rules.connect(), rules.variant())
+            }
+            Message::Move(next_move) => write!(f, "MOVE {}", next_move),
+            Message::Rematch => write!(f, "REMATCH"),
+            Message::Reject(next_move, reason) => write!(f, "REJECT {} {}", next_move, reason),
+            Message::Error(reason) => write!(f, "ERROR {}", reason),
+            Message::Bye => write!(f, "BYE"),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = NetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NetError::InvalidMessage(s.to_string());
+        let (command, argument) = s.split_once(' ').unwrap_or((s, ""));
+        match command {
+            "HELLO" => argument.parse().map(Message::Hello).map_err(|_| invalid()),
+            "RULES" => {
+                let mut fields = argument.split(' ');
+                let mut field = || fields.next().ok_or_else(invalid);
+                let size = field()?.parse().map_err(|_| invalid())?;
+                let connect = field()?.parse().map_err(|_| invalid())?;
+                let variant: Variant = field()?.parse().map_err(|_| invalid())?;
+                let rules = Rules::new(variant, connect).map_err(|_| invalid())?;
+                Ok(Message::Rules(size, rules))
+            }
+            "MOVE" => argument.parse().map(Message::Move).map_err(|_| invalid()),
+            "REMATCH" => Ok(Message::Rematch),
+            "REJECT" => {
+                let (next_move, reason) = argument.split_once(' ').unwrap_or((argument, ""));
+                let next_move = next_move.parse().map_err(|_| invalid())?;
+                Ok(Message::Reject(next_move, reason.to_string()))
+            }
+            "ERROR" => Ok(Message::Error(argument.to_string())),
+            "BYE" => Ok(Message::Bye),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// TCP connection to another instance that has completed the protocol handshake.
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    /// Waits for a peer to connect to `listener`, giving up once `cancel` is set.
+    pub fn accept(listener: &TcpListener, cancel: &AtomicBool) -> Result<Self, NetError> {
+        listener.set_nonblocking(true)?;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    return Self::handshake(stream);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if cancel.load(Ordering::Relaxed) {
+                        return Err(NetError::Cancelled);
+                    }
+                    thread::sleep(ACCEPT_POLL_RATE);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    pub fn join(addr: impl ToSocketAddrs) -> Result<Self, NetError> {
+        Self::handshake(TcpStream::connect(addr)?)
+    }
+
+    fn handshake(stream: TcpStream) -> Result<Self, NetError> {
+        let mut connection = Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+        connection.send(&Message::Hello(PROTOCOL_VERSION))?;
+        match connection.receive()? {
+            Message::Hello(PROTOCOL_VERSION) => Ok(connection),
+            Message::Hello(version) => Err(NetError::VersionMismatch(version)),
+            message => Err(NetError::InvalidMessage(message.to_string())),
+        }
+    }
+
+    /// Second handle to the same connection, so one thread can block on `receive`
+    /// while another sends.
+    pub fn try_clone(&self) -> Result<Self, NetError> {
+        let stream = self.writer.try_clone()?;
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    /// Closes the connection for every handle, waking up any thread blocked on `receive`.
+    pub fn shutdown(&self) {
+        let _ = self.writer.shutdown(Shutdown::Both);
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), NetError> {
+        writeln!(self.writer, "{}", message)?;
+        Ok(())
+    }
+
+    /// Blocks until the next message arrives. A closed connection is reported as
+    /// `NetError::Disconnected`.
+    pub fn receive(&mut self) -> Result<Message, NetError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(NetError::Disconnected);
+        }
+        line.trim_end().parse()
+    }
+}
+
+/// What a message from the peer meant for the game.
+#[derive(Debug, PartialEq)]
+pub enum RemoteEvent {
+    /// The host set the board size and rules, starting over on an empty board.
+    RulesSet,
+    Moved(Move),
+    RematchRequested,
+    RematchStarted,
+    /// The peer refused a message. A refused local move the peer had not answered yet is
+    /// taken back.
+    Rejected(String),
+    Disconnected,
+}
+
+/// State of a game against a peer, validating both sides' moves independently of the
+/// connection they travel over.
+pub struct NetworkGame {
+    board: ConnectFourBoard,
+    local_player: Player,
+    /// Local move the peer has not answered with a move of its own yet, while the game is
+    /// still going.
+    unanswered: Option<Move>,
+    local_rematch: bool,
+    remote_rematch: bool,
+}
+
+impl NetworkGame {
+    /// Game on a board of `size` played by `rules`. The host sends them to the peer with
+    /// `rules_message`, whose game takes them over.
+    pub fn new(local_player: Player, size: BoardSize, rules: Rules) -> Self {
+        Self {
+            board: ConnectFourBoard::with_rules(size, rules),
+            local_player,
+            unanswered: None,
+            local_rematch: false,
+            remote_rematch: false,
+        }
+    }
+
+    pub fn board(&self) -> &ConnectFourBoard {
+        &self.board
+    }
+
+    pub fn local_player(&self) -> Player {
+        self.local_player
+    }
+
+    pub fn is_local_turn(&self) -> bool {
+        self.board.status() == BoardStatus::OnGoing
+            && self.board.current_player() == self.local_player
+    }
+
+    pub fn rematch_requested(&self) -> bool {
+        self.remote_rematch
+    }
+
+    /// Message telling the peer the board size and rules of this game.
+    pub fn rules_message(&self) -> Message {
+        Message::Rules(self.board.size(), self.board.rules())
+    }
+
+    /// Plays a local move, returning the message to send to the peer.
+    pub fn play(&mut self, next_move: Move) -> Result<Message, NetError> {
+        if !self.is_local_turn() {
+            return Err(NetError::NotYourTurn);
+        }
+        self.board
+            .try_play(next_move)
+            .map_err(|_| NetError::IllegalMove(next_move))?;
+        // A move that ends the game is never answered with another one.
+        self.unanswered = (self.board.status() == BoardStatus::OnGoing).then_some(next_move);
+        Ok(Message::Move(next_move))
+    }
+
+    /// Asks for a rematch once the game is over, returning the message to send to the
+    /// peer. The rematch starts once both sides have asked.
+    pub fn request_rematch(&mut self) -> Result<Message, NetError> {
+        if self.board.status() == BoardStatus::OnGoing {
+            return Err(NetError::GameNotOver);
+        }
+        self.local_rematch = true;
+        self.start_rematch();
+        Ok(Message::Rematch)
+    }
+
+    /// Applies a message received from the peer. Illegal moves, rules set after the first
+    /// move and rematches asked for during a game are returned as errors so they can be
+    /// reported back to the peer.
+    pub fn handle(&mut self, message: Message) -> Result<RemoteEvent, NetError> {
+        match message {
+            Message::Rules(size, rules) => {
+                if self.board.move_count() > 0 {
+                    return Err(NetError::GameStarted);
+                }
+                self.board = ConnectFourBoard::with_rules(size, rules);
+                Ok(RemoteEvent::RulesSet)
+            }
+            Message::Move(next_move) => {
+                if self.board.status() != BoardStatus::OnGoing
+                    || self.board.current_player() == self.local_player
+                {
+                    return Err(NetError::IllegalMove(next_move));
+                }
+                self.board
+                    .try_play(next_move)
+                    .map_err(|_| NetError::IllegalMove(next_move))?;
+                self.unanswered = None;
+                Ok(RemoteEvent::Moved(next_move))
+            }
+            Message::Rematch => {
+                if self.board.status() == BoardStatus::OnGoing {
+                    return Err(NetError::GameNotOver);
+                }
+                self.remote_rematch = true;
+                if self.start_rematch() {
+                    Ok(RemoteEvent::RematchStarted)
+                } else {
+                    Ok(RemoteEvent::RematchRequested)
+                }
+            }
+            // The peer never applied the move, so it is taken back to keep both boards
+            // the same and let the local player move again.
+            Message::Reject(next_move, reason) => {
+                if self.unanswered == Some(next_move) {
+                    self.unanswered = None;
+                    self.board.pop_move();
+                }
+                Ok(RemoteEvent::Rejected(reason))
+            }
+            Message::Error(reason) => Ok(RemoteEvent::Rejected(reason)),
+            Message::Bye => Ok(RemoteEvent::Disconnected),
+            Message::Hello(_) => Err(NetError::InvalidMessage(message.to_string())),
+        }
+    }
+
+    // Colors swap between games so both sides take turns moving first.
+    fn start_rematch(&mut self) -> bool {
+        if !(self.local_rematch && self.remote_rematch) {
+            return false;
+        }
+        self.board = ConnectFourBoard::with_rules(self.board.size(), self.board.rules());
+        self.unanswered = None;
+        self.local_player = match self.local_player {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        };
+        self.local_rematch = false;
+        self.remote_rematch = false;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, sync::atomic::AtomicBool, thread};
+
+    use crate::game::{BoardSize, Column, Move, Player, Rules, Variant};
+
+    use super::{Connection, Message, NetError, NetworkGame, RemoteEvent};
+
+    fn connect() -> (Connection, Connection) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let host = thread::spawn(move || Connection::accept(&listener, &AtomicBool::new(false)));
+        let guest = Connection::join(addr).unwrap();
+        (host.join().unwrap().unwrap(), guest)
+    }
+
+    fn standard_game(local_player: Player) -> NetworkGame {
+        NetworkGame::new(local_player, BoardSize::STANDARD, Rules::STANDARD)
+    }
+
+    fn relay(from: &mut Connection, message: Message, to: &mut Connection) -> Message {
+        from.send(&message).unwrap();
+        to.receive().unwrap()
+    }
+
+    #[test]
+    fn parses_messages() {
+        for message in [
+            Message::Hello(1),
+            Message::Rules(
+                BoardSize::new(9, 7).unwrap(),
+                Rules::new(Variant::PopOut, 5).unwrap(),
+            ),
+            Message::Move(Move::Drop(Column::Four)),
+            Message::Move(Move::Pop(Column::Two)),
+            Message::Rematch,
+            Message::Reject(Move::Drop(Column::Eight), "Move 8 is not legal.".into()),
+            Message::Error("Move 8 is not legal.".into()),
+            Message::Bye,
+        ] {
+            assert_eq!(message, message.to_string().parse().unwrap());
+        }
+        assert!("MOVE 0".parse::<Message>().is_err());
+        assert!("RULES 7x6 6 standard".parse::<Message>().is_err());
+        assert!("RULES 7x6 4".parse::<Message>().is_err());
+        assert!("JUMP".parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn plays_game_over_loopback() {
+        let (mut host_conn, mut guest_conn) = connect();
+        let mut host = standard_game(Player::One);
+        let mut guest = standard_game(Player::Two);
+
+        for column in [Column::One, Column::Two, Column::One, Column::Two] {
+            let next_move = Move::Drop(column);
+            let (sender, sender_conn, receiver, receiver_conn) = if host.is_local_turn() {
+                (&mut host, &mut host_conn, &mut guest, &mut guest_conn)
+            } else {
+                (&mut guest, &mut guest_conn, &mut host, &mut host_conn)
+            };
+            let message = relay(sender_conn, sender.play(next_move).unwrap(), receiver_conn);
+            assert_eq!(
+                RemoteEvent::Moved(next_move),
+                receiver.handle(message).unwrap()
+            );
+        }
+
+        assert_eq!("1212", host.board().to_moves());
+        assert_eq!("1212", guest.board().to_moves());
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let mut host = standard_game(Player::One);
+        let mut guest = standard_game(Player::Two);
+        let one = Move::Drop(Column::One);
+
+        assert!(matches!(guest.play(one), Err(NetError::NotYourTurn)));
+        assert!(matches!(
+            host.handle(Message::Move(one)),
+            Err(NetError::IllegalMove(_))
+        ));
+
+        for _ in 0..3 {
+            guest.handle(host.play(one).unwrap()).unwrap();
+            host.handle(guest.play(one).unwrap()).unwrap();
+        }
+        assert!(matches!(host.play(one), Err(NetError::IllegalMove(_))));
+        // Popping is not part of the standard rules.
+        assert!(matches!(
+            host.play(Move::Pop(Column::One)),
+            Err(NetError::IllegalMove(_))
+        ));
+    }
+
+    #[test]
+    fn takes_back_rejected_moves() {
+        let mut host = standard_game(Player::One);
+        let mut guest = standard_game(Player::Two);
+        let (three, four) = (Move::Drop(Column::Three), Move::Drop(Column::Four));
+        guest.handle(host.play(four).unwrap()).unwrap();
+        host.handle(guest.play(four).unwrap()).unwrap();
+        host.play(three).unwrap();
+
+        // Errors about anything but the unanswered move leave it on the board.
+        let reason = "Move 3 is not legal.".to_string();
+        for message in [
+            Message::Error(reason.clone()),
+            Message::Reject(four, reason.clone()),
+        ] {
+            assert_eq!(
+                RemoteEvent::Rejected(reason.clone()),
+                host.handle(message).unwrap()
+            );
+            assert_eq!("443", host.board().to_moves());
+        }
+
+        assert_eq!(
+            RemoteEvent::Rejected(reason.clone()),
+            host.handle(Message::Reject(three, reason.clone())).unwrap()
+        );
+        assert_eq!("44", host.board().to_moves());
+        assert!(host.is_local_turn());
+
+        // Only the unanswered move is taken back.
+        host.handle(Message::Reject(three, reason)).unwrap();
+        assert_eq!("44", host.board().to_moves());
+    }
+
+    #[test]
+    fn keeps_winning_moves() {
+        let mut host = standard_game(Player::One);
+        let mut guest = standard_game(Player::Two);
+        for _ in 0..3 {
+            guest
+                .handle(host.play(Move::Drop(Column::Four)).unwrap())
+                .unwrap();
+            host.handle(guest.play(Move::Drop(Column::Five)).unwrap())
+                .unwrap();
+        }
+        host.play(Move::Drop(Column::Four)).unwrap();
+
+        // The game is over, so no later message can be answering the winning move.
+        let reason = "Move 4 is not legal.".to_string();
+        host.handle(Message::Reject(Move::Drop(Column::Four), reason))
+            .unwrap();
+        assert_eq!("4545454", host.board().to_moves());
+    }
+
+    #[test]
+    fn plays_by_the_host_rules() {
+        let (mut host_conn, mut guest_conn) = connect();
+        let rules = Rules::new(Variant::PopOut, 3).unwrap();
+        let mut host = NetworkGame::new(Player::One, BoardSize::new(5, 4).unwrap(), rules);
+        let mut guest = standard_game(Player::Two);
+
+        let message = relay(&mut host_conn, host.rules_message(), &mut guest_conn);
+        assert_eq!(RemoteEvent::RulesSet, guest.handle(message).unwrap());
+        assert_eq!(host.board().size(), guest.board().size());
+        assert_eq!(rules, guest.board().rules());
+
+        guest
+            .handle(host.play(Move::Drop(Column::One)).unwrap())
+            .unwrap();
+        host.handle(guest.play(Move::Drop(Column::Two)).unwrap())
+            .unwrap();
+        let message = relay(
+            &mut host_conn,
+            host.play(Move::Pop(Column::One)).unwrap(),
+            &mut guest_conn,
+        );
+        assert_eq!(
+            RemoteEvent::Moved(Move::Pop(Column::One)),
+            guest.handle(message).unwrap()
+        );
+        assert_eq!(host.board().to_moves(), guest.board().to_moves());
+
+        assert!(matches!(
+            guest.handle(host.rules_message()),
+            Err(NetError::GameStarted)
+        ));
+    }
+
+    #[test]
+    fn swaps_colors_on_rematch() {
+        let (mut host_conn, mut guest_conn) = connect();
+        let rules = Rules::new(Variant::Standard, 3).unwrap();
+        let mut host = NetworkGame::new(Player::One, BoardSize::STANDARD, rules);
+        let mut guest = NetworkGame::new(Player::Two, BoardSize::STANDARD, rules);
+        guest
+            .handle(host.play(Move::Drop(Column::Four)).unwrap())
+            .unwrap();
+
+        assert!(matches!(host.request_rematch(), Err(NetError::GameNotOver)));
+        assert!(matches!(
+            guest.handle(Message::Rematch),
+            Err(NetError::GameNotOver)
+        ));
+        // The host completes a row of three on the bottom.
+        for column in [Column::Five, Column::Six] {
+            host.handle(guest.play(Move::Drop(Column::One)).unwrap())
+                .unwrap();
+            guest
+                .handle(host.play(Move::Drop(column)).unwrap())
+                .unwrap();
+        }
+
+        let message = relay(
+            &mut host_conn,
+            host.request_rematch().unwrap(),
+            &mut guest_conn,
+        );
+        assert_eq!(
+            RemoteEvent::RematchRequested,
+            guest.handle(message).unwrap()
+        );
+        let message = relay(
+            &mut guest_conn,
+            guest.request_rematch().unwrap(),
+            &mut host_conn,
+        );
+        assert_eq!(RemoteEvent::RematchStarted, host.handle(message).unwrap());
+
+        assert_eq!(Player::Two, host.local_player());
+        assert_eq!(Player::One, guest.local_player());
+        assert_eq!("", host.board().to_moves());
+        assert_eq!("", guest.board().to_moves());
+        // The rematch keeps the rules of the first game.
+        assert_eq!(rules, host.board().rules());
+        assert!(guest.is_local_turn());
+    }
+
+    #[test]
+    fn detects_disconnects() {
+        let (host_conn, mut guest_conn) = connect();
+        drop(host_conn);
+        assert!(matches!(guest_conn.receive(), Err(NetError::Disconnected)));
+    }
+}
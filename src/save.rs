@@ -0,0 +1,203 @@
+use std::{env, fs, path::PathBuf, str::FromStr};
+
+use connect_fors::{
+    ai::AiLevel,
+    game::{BoardSize, ConnectFourBoard, Rules, Variant},
+};
+use thiserror::Error;
+
+use crate::app::Page;
+
+const SAVE_FILE: &str = "save.txt";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SaveError {
+    #[error("Save file is missing the '{0}' entry.")]
+    MissingEntry(&'static str),
+
+    #[error("Save file has an invalid '{0}' entry.")]
+    InvalidEntry(&'static str),
+
+    #[error("Only local games in progress can be saved.")]
+    NoGame,
+}
+
+/// Everything needed to resume a game, stored as `key=value` lines.
+pub struct SavedGame {
+    pub page: Page,
+    pub ai_level: AiLevel,
+    pub player_names: [String; 2],
+    pub board: ConnectFourBoard,
+}
+
+impl SavedGame {
+    pub fn write(&self) -> anyhow::Result<PathBuf> {
+        let path = save_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, self.serialize()?)?;
+        Ok(path)
+    }
+
+    pub fn read() -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(save_path()?)?;
+        Ok(contents.parse()?)
+    }
+
+    fn serialize(&self) -> Result<String, SaveError> {
+        let mode = match self.page {
+            Page::SinglePlayer => "singleplayer",
+            Page::MultiPlayer => "multiplayer",
+            _ => return Err(SaveError::NoGame),
+        };
+        Ok(format!(
+            "mode={}\nlevel={}\nplayer_one={}\nplayer_two={}\nsize={}\nvariant={}\nconnect={}\nmoves={}\n",
+            mode,
+            self.ai_level,
+            self.player_names[0],
+            self.player_names[1],
+            self.board.size(),
+            self.board.variant(),
+            self.board.connect(),
+            self.board.to_moves(),
+        ))
+    }
+}
+
+impl FromStr for SavedGame {
+    type Err = SaveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entry = |key: &'static str| {
+            s.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .ok_or(SaveError::MissingEntry(key))
+        };
+
+        let page = match entry("mode")? {
+            "singleplayer" => Page::SinglePlayer,
+            "multiplayer" => Page::MultiPlayer,
+            _ => return Err(SaveError::InvalidEntry("mode")),
+        };
+        // Saves from before boards could be resized are always 7x6.
+        let size = match entry("size") {
+            Ok(size) => size.parse().map_err(|_| SaveError::InvalidEntry("size"))?,
+            Err(_) => BoardSize::STANDARD,
+        };
+        // As are their rules, which predate PopOut and lines other than four.
+        let variant = match entry("variant") {
+            Ok(variant) => variant
+                .parse()
+                .map_err(|_| SaveError::InvalidEntry("variant"))?,
+            Err(_) => Variant::Standard,
+        };
+        let connect = match entry("connect") {
+            Ok(connect) => connect
+                .parse()
+                .map_err(|_| SaveError::InvalidEntry("connect"))?,
+            Err(_) => Rules::STANDARD.connect(),
+        };
+        let rules = Rules::new(variant, connect).map_err(|_| SaveError::InvalidEntry("connect"))?;
+        let mut board = ConnectFourBoard::with_rules(size, rules);
+        board
+            .play_moves(entry("moves")?)
+            .map_err(|_| SaveError::InvalidEntry("moves"))?;
+        Ok(Self {
+            page,
+            ai_level: entry("level")?
+                .parse()
+                .map_err(|_| SaveError::InvalidEntry("level"))?,
+            player_names: [entry("player_one")?.into(), entry("player_two")?.into()],
+            board,
+        })
+    }
+}
+
+fn save_path() -> anyhow::Result<PathBuf> {
+    data_path(SAVE_FILE)
+}
+
+/// Location of one of the game's data files, following the XDG base directory specification.
+pub fn data_path(file: &str) -> anyhow::Result<PathBuf> {
+    let data_dir = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = env::var_os("HOME")
+                .ok_or_else(|| anyhow::anyhow!("Could not locate the home directory."))?;
+            PathBuf::from(home).join(".local").join("share")
+        }
+    };
+    Ok(data_dir.join("connect-fors").join(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use connect_fors::{
+        ai::AiLevel,
+        game::{BoardSize, ConnectFourBoard, Rules, Variant},
+    };
+
+    use crate::app::Page;
+
+    use super::{SaveError, SavedGame};
+
+    #[test]
+    fn round_trips_saved_game() {
+        let game = SavedGame {
+            page: Page::SinglePlayer,
+            ai_level: AiLevel::Hard,
+            player_names: ["Alice".into(), "Bob".into()],
+            board: ConnectFourBoard::from_moves_with_size("4483", BoardSize::new(8, 7).unwrap())
+                .unwrap(),
+        };
+
+        let loaded: SavedGame = game.serialize().unwrap().parse().unwrap();
+        assert!(loaded.page == Page::SinglePlayer);
+        assert_eq!(AiLevel::Hard, loaded.ai_level);
+        assert_eq!(["Alice", "Bob"], loaded.player_names);
+        assert_eq!("4483", loaded.board.to_moves());
+        assert_eq!("8x7", loaded.board.size().to_string());
+    }
+
+    #[test]
+    fn round_trips_popout_game() {
+        let rules = Rules::new(Variant::PopOut, 5).unwrap();
+        let mut board = ConnectFourBoard::with_rules(BoardSize::STANDARD, rules);
+        board.play_moves("4455p4").unwrap();
+        let game = SavedGame {
+            page: Page::MultiPlayer,
+            ai_level: AiLevel::Easy,
+            player_names: ["Alice".into(), "Bob".into()],
+            board,
+        };
+
+        let loaded: SavedGame = game.serialize().unwrap().parse().unwrap();
+        assert_eq!(rules, loaded.board.rules());
+        assert_eq!("4455p4", loaded.board.to_moves());
+    }
+
+    #[test]
+    fn loads_standard_board_without_size() {
+        let game: SavedGame =
+            "mode=multiplayer\nlevel=Easy\nplayer_one=A\nplayer_two=B\nmoves=44\n"
+                .parse()
+                .unwrap();
+        assert_eq!(BoardSize::STANDARD, game.board.size());
+        assert_eq!(Rules::STANDARD, game.board.rules());
+    }
+
+    #[test]
+    fn error_loading_incomplete_save() {
+        let result =
+            "mode=multiplayer\nlevel=Easy\nplayer_one=A\nplayer_two=B\n".parse::<SavedGame>();
+        assert_eq!(Some(SaveError::MissingEntry("moves")), result.err());
+    }
+
+    #[test]
+    fn error_loading_invalid_moves() {
+        let result = "mode=multiplayer\nlevel=Easy\nplayer_one=A\nplayer_two=B\nmoves=48\n"
+            .parse::<SavedGame>();
+        assert_eq!(Some(SaveError::InvalidEntry("moves")), result.err());
+    }
+}
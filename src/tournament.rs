@@ -0,0 +1,508 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use rand::seq::IndexedRandom;
+use thiserror::Error;
+
+use crate::{
+    ai::{EvalWeights, Evaluation, MinimaxStrategy, Strategy, TranspositionTable},
+    game::{BoardSize, BoardStatus, Column, ConnectFourBoard, Player, Rules},
+};
+
+// Each game creates two searches per thread, so their tables are kept small.
+const TABLE_SIZE: usize = 1 << 16;
+
+// Random openings that run into a decided game start over at most this many times.
+const OPENING_ATTEMPTS: u32 = 1000;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TournamentError {
+    #[error("Setting '{0}' is not a valid key=value pair.")]
+    InvalidSetting(String),
+
+    #[error("Unknown setting '{0}'.")]
+    UnknownSetting(String),
+
+    #[error("Setting '{0}' has an invalid value '{1}'.")]
+    InvalidValue(String, String),
+
+    #[error("A tournament needs at least two players.")]
+    NotEnoughPlayers,
+
+    #[error("No opening of {0} random moves leaves the game undecided.")]
+    OpeningTooLong(u8),
+}
+
+/// Settings of a tournament player, written as comma separated `key=value` pairs such as
+/// `depth=6,threats=4,random=0.1`. Left out settings keep their default values.
+///
+/// The `position` and `threats` weights only apply to the table evaluation, which
+/// `eval=threats` swaps for the threat-aware one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerConfig {
+    pub name: String,
+    pub depth: u8,
+    pub evaluation: Evaluation,
+    pub weights: EvalWeights,
+    /// Probability of playing a random weaker move instead of the best one found.
+    pub randomness: f64,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            name: "depth=6".into(),
+            depth: 6,
+            evaluation: Evaluation::default(),
+            weights: EvalWeights::default(),
+            randomness: 0.0,
+        }
+    }
+}
+
+impl FromStr for PlayerConfig {
+    type Err = TournamentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = PlayerConfig {
+            name: s.to_string(),
+            ..Default::default()
+        };
+        for setting in s.split(',') {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| TournamentError::InvalidSetting(setting.into()))?;
+            let invalid = || TournamentError::InvalidValue(key.into(), value.into());
+            match key {
+                "depth" => config.depth = value.parse().map_err(|_| invalid())?,
+                "eval" => config.evaluation = value.parse().map_err(|_| invalid())?,
+                "position" => config.weights.position = value.parse().map_err(|_| invalid())?,
+                "threats" => config.weights.threats = value.parse().map_err(|_| invalid())?,
+                "random" => {
+                    config.randomness = value
+                        .parse()
+                        .ok()
+                        .filter(|chance| (0.0..=1.0).contains(chance))
+                        .ok_or_else(invalid)?;
+                }
+                _ => return Err(TournamentError::UnknownSetting(key.into())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl PlayerConfig {
+    fn strategy(&self) -> MinimaxStrategy {
+        let evaluator = match self.evaluation {
+            Evaluation::Table => Box::new(self.weights),
+            evaluation => evaluation.evaluator(),
+        };
+        MinimaxStrategy::with_options(
+            self.depth,
+            evaluator,
+            self.randomness,
+            TranspositionTable::new(TABLE_SIZE),
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TournamentOptions {
+    /// Number of openings every pair of players plays, once with each color.
+    pub rounds: u32,
+    /// Number of random moves played before the players take over.
+    pub opening_moves: u8,
+    pub threads: usize,
+    pub size: BoardSize,
+    pub rules: Rules,
+}
+
+impl Default for TournamentOptions {
+    fn default() -> Self {
+        Self {
+            rounds: 10,
+            opening_moves: 2,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            size: BoardSize::STANDARD,
+            rules: Rules::STANDARD,
+        }
+    }
+}
+
+/// Games between two players, from the point of view of the first.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Record {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Record {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Wins count as one point and draws as half a point.
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    fn add(&mut self, other: Record) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// Outcome of a round robin tournament.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentReport {
+    pub players: Vec<String>,
+    /// Record of every player against every other player, indexed by player.
+    pub records: Vec<Vec<Record>>,
+}
+
+impl TournamentReport {
+    /// Record of a player against the whole field.
+    pub fn total(&self, player: usize) -> Record {
+        let mut total = Record::default();
+        for record in &self.records[player] {
+            total.add(*record);
+        }
+        total
+    }
+
+    /// Elo ratings that best explain the results, relative to an average of zero.
+    ///
+    /// Every rating is repeatedly moved halfway towards its performance rating, the average
+    /// rating of the opponents played plus the rating difference implied by the score
+    /// against them. Moving all the way makes the ratings of two players swap forever.
+    pub fn elo(&self) -> Vec<f64> {
+        let count = self.players.len();
+        let mut ratings = vec![0.0; count];
+        for _ in 0..100 {
+            let mut next = ratings.clone();
+            for (player, rating) in next.iter_mut().enumerate() {
+                let total = self.total(player);
+                let games = total.games() as f64;
+                if games == 0.0 {
+                    continue;
+                }
+                let opponents = (0..count)
+                    .map(|opponent| {
+                        self.records[player][opponent].games() as f64 * ratings[opponent]
+                    })
+                    .sum::<f64>()
+                    / games;
+                // Perfect scores would imply an infinite difference, so they are capped at
+                // half a game short of perfect.
+                let score = (total.points() / games).clamp(0.5 / games, 1.0 - 0.5 / games);
+                let performance = opponents + 400.0 * (score / (1.0 - score)).log10();
+                *rating = (*rating + performance) / 2.0;
+            }
+            let mean = next.iter().sum::<f64>() / count as f64;
+            ratings = next.into_iter().map(|rating| rating - mean).collect();
+        }
+        ratings
+    }
+}
+
+/// A single game of the tournament, between the players at the given indexes.
+struct Pairing {
+    first: usize,
+    second: usize,
+    opening: Vec<Column>,
+}
+
+/// Plays every player against every other player, with each pair playing `rounds` random
+/// openings once with each color. Games run in parallel on `options.threads` threads.
+pub fn run(
+    players: &[PlayerConfig],
+    options: TournamentOptions,
+) -> Result<TournamentReport, TournamentError> {
+    if players.len() < 2 {
+        return Err(TournamentError::NotEnoughPlayers);
+    }
+    if options.opening_moves >= options.size.slots() {
+        return Err(TournamentError::OpeningTooLong(options.opening_moves));
+    }
+
+    let mut pairings = Vec::new();
+    for a in 0..players.len() {
+        for b in a + 1..players.len() {
+            for _ in 0..options.rounds {
+                let opening = random_opening(options.opening_moves, options.size, options.rules)
+                    .ok_or(TournamentError::OpeningTooLong(options.opening_moves))?;
+                pairings.push(Pairing {
+                    first: a,
+                    second: b,
+                    opening: opening.clone(),
+                });
+                pairings.push(Pairing {
+                    first: b,
+                    second: a,
+                    opening,
+                });
+            }
+        }
+    }
+
+    let records = Mutex::new(vec![vec![Record::default(); players.len()]; players.len()]);
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            scope.spawn(|| {
+                while let Some(pairing) = pairings.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let winner = play_game(
+                        &mut players[pairing.first].strategy(),
+                        &mut players[pairing.second].strategy(),
+                        &pairing.opening,
+                        options.size,
+                        options.rules,
+                    );
+                    let mut records = records.lock().unwrap();
+                    let (first, second) = (pairing.first, pairing.second);
+                    match winner {
+                        Some(Player::One) => {
+                            records[first][second].wins += 1;
+                            records[second][first].losses += 1;
+                        }
+                        Some(Player::Two) => {
+                            records[first][second].losses += 1;
+                            records[second][first].wins += 1;
+                        }
+                        None => {
+                            records[first][second].draws += 1;
+                            records[second][first].draws += 1;
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    Ok(TournamentReport {
+        players: players.iter().map(|player| player.name.clone()).collect(),
+        records: records.into_inner().unwrap(),
+    })
+}
+
+/// Plays a game from `opening` onwards, returning the winner or `None` for a draw. A
+/// strategy that has no move to offer forfeits the game.
+fn play_game(
+    first: &mut impl Strategy,
+    second: &mut impl Strategy,
+    opening: &[Column],
+    size: BoardSize,
+    rules: Rules,
+) -> Option<Player> {
+    let mut board = ConnectFourBoard::with_rules(size, rules);
+    for &column in opening {
+        board
+            .try_move(column)
+            .expect("openings only contain legal moves");
+    }
+
+    loop {
+        match board.status() {
+            BoardStatus::Winner(player) => return Some(player),
+            BoardStatus::Draw => return None,
+            BoardStatus::OnGoing => {}
+        }
+
+        let player = board.current_player();
+        let next_move = match player {
+            Player::One => first.choose(&board),
+            Player::Two => second.choose(&board),
+        };
+        if next_move.is_none_or(|next_move| board.try_play(next_move).is_err()) {
+            return Some(player.opponent());
+        }
+    }
+}
+
+/// Random drops that leave the game undecided. An opening that runs into a position where
+/// every drop ends the game starts over, and `None` is returned once too many have.
+fn random_opening(moves: u8, size: BoardSize, rules: Rules) -> Option<Vec<Column>> {
+    let mut rng = rand::rng();
+    let mut board = ConnectFourBoard::with_rules(size, rules);
+    let mut attempts = 1;
+    while board.move_count() < moves as u16 {
+        let undecided: Vec<_> = size
+            .columns()
+            .filter(|&column| keeps_game_going(&mut board, column))
+            .collect();
+        match undecided.choose(&mut rng) {
+            Some(&column) => {
+                board.try_move(column).expect("column is playable");
+            }
+            None if attempts < OPENING_ATTEMPTS => {
+                board = ConnectFourBoard::with_rules(size, rules);
+                attempts += 1;
+            }
+            None => return None,
+        }
+    }
+
+    Some(
+        board
+            .history()
+            .iter()
+            .map(|opening_move| opening_move.column())
+            .collect(),
+    )
+}
+
+/// Whether dropping a disc in `column` is legal and leaves the game undecided.
+fn keeps_game_going(board: &mut ConnectFourBoard, column: Column) -> bool {
+    if board.try_move(column).is_err() {
+        return false;
+    }
+    let ongoing = board.status() == BoardStatus::OnGoing;
+    board.pop_move();
+    ongoing
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ai::{EvalWeights, Evaluation},
+        game::{BoardSize, BoardStatus, ConnectFourBoard, Rules},
+    };
+
+    use super::{
+        PlayerConfig, Record, TournamentError, TournamentOptions, TournamentReport, random_opening,
+        run,
+    };
+
+    #[test]
+    fn parses_player_configs() {
+        let config: PlayerConfig = "depth=4,threats=8,random=0.25".parse().unwrap();
+        assert_eq!(
+            PlayerConfig {
+                name: "depth=4,threats=8,random=0.25".into(),
+                depth: 4,
+                evaluation: Evaluation::Table,
+                weights: EvalWeights {
+                    position: 1,
+                    threats: 8,
+                },
+                randomness: 0.25,
+            },
+            config
+        );
+
+        assert_eq!(
+            Evaluation::Threats,
+            "eval=threats".parse::<PlayerConfig>().unwrap().evaluation
+        );
+        assert_eq!(
+            TournamentError::UnknownSetting("speed".into()),
+            "speed=2".parse::<PlayerConfig>().unwrap_err()
+        );
+        assert_eq!(
+            TournamentError::InvalidValue("random".into(), "2".into()),
+            "random=2".parse::<PlayerConfig>().unwrap_err()
+        );
+        assert_eq!(
+            TournamentError::InvalidSetting("depth".into()),
+            "depth".parse::<PlayerConfig>().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn plays_round_robin_with_both_colors() {
+        let players: Vec<PlayerConfig> = ["depth=1", "depth=2", "depth=3"]
+            .into_iter()
+            .map(|config| config.parse().unwrap())
+            .collect();
+        let options = TournamentOptions {
+            rounds: 2,
+            opening_moves: 2,
+            threads: 2,
+            ..Default::default()
+        };
+        let report = run(&players, options).unwrap();
+
+        for player in 0..3 {
+            assert_eq!(8, report.total(player).games());
+            for opponent in 0..3 {
+                let record = report.records[player][opponent];
+                let reverse = report.records[opponent][player];
+                assert_eq!(record.wins, reverse.losses);
+                assert_eq!(record.draws, reverse.draws);
+            }
+        }
+        assert_eq!(
+            TournamentError::NotEnoughPlayers,
+            run(&players[..1], options).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn openings_leave_games_undecided() {
+        let size = BoardSize::new(4, 4).unwrap();
+        for _ in 0..10 {
+            let opening = random_opening(15, size, Rules::STANDARD).unwrap();
+            let mut board = ConnectFourBoard::new(size);
+            for column in opening {
+                board.try_move(column).unwrap();
+            }
+            assert_eq!(15, board.move_count());
+            assert_eq!(BoardStatus::OnGoing, board.status());
+        }
+
+        let players: Vec<PlayerConfig> = vec!["depth=1".parse().unwrap(); 2];
+        let options = TournamentOptions {
+            opening_moves: 42,
+            ..Default::default()
+        };
+        assert_eq!(
+            TournamentError::OpeningTooLong(42),
+            run(&players, options).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn stronger_results_rate_higher() {
+        let report = TournamentReport {
+            players: vec!["a".into(), "b".into()],
+            records: vec![
+                vec![
+                    Record::default(),
+                    Record {
+                        wins: 3,
+                        draws: 0,
+                        losses: 1,
+                    },
+                ],
+                vec![
+                    Record {
+                        wins: 1,
+                        draws: 0,
+                        losses: 3,
+                    },
+                    Record::default(),
+                ],
+            ],
+        };
+        let elo = report.elo();
+        // A 75% score is worth about 191 Elo.
+        assert!((elo[0] - elo[1] - 190.8).abs() < 1.0);
+        assert!((elo[0] + elo[1]).abs() < 1e-9);
+    }
+}
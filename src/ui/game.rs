@@ -12,10 +12,9 @@ use ratatui::{
     },
 };
 
-use crate::{
-    app::App,
-    game::{BoardStatus, Column, ConnectFourBoard, Player, Slot},
-};
+use connect_fors::game::{BoardSize, BoardStatus, Column, ConnectFourBoard, Player, Slot, Variant};
+
+use crate::app::{App, Page};
 
 use super::util;
 
@@ -25,14 +24,19 @@ const SLOT: f64 = SLOT_RADIUS + SLOT_PADDING;
 
 const BOARD_PADDING: f64 = 15.0;
 
+const WINNING_LINE_COLOR: Color = Color::LightGreen;
+
 pub fn draw(frame: &mut Frame, app: &App) {
     let chunks = game_page_chunks(frame);
 
-    let title = Line::from(vec![
-        "Player One".red(),
-        "  VS  ".into(),
-        "Player Two".yellow(),
-    ]);
+    let [player_one, player_two] = match app.spectate() {
+        Some(spectate) if app.current_page() == Page::Spectate => [
+            format!("AI ({})", spectate.level(Player::One)),
+            format!("AI ({})", spectate.level(Player::Two)),
+        ],
+        _ => app.player_names().clone(),
+    };
+    let title = Line::from(vec![player_one.red(), "  VS  ".into(), player_two.yellow()]);
     let title_area = util::center(
         chunks[0],
         Constraint::Length(title.width() as u16),
@@ -45,7 +49,8 @@ pub fn draw(frame: &mut Frame, app: &App) {
         BoardStatus::Draw => Color::Gray,
         BoardStatus::OnGoing => Color::White,
     };
-    let board_area = board_chunk(chunks[1]);
+    let size = app.board().size();
+    let board_area = board_chunk(chunks[1], size);
     let canvas = Canvas::default()
         .block(Block::bordered().fg(border_color))
         .marker(Marker::Braille)
@@ -54,11 +59,54 @@ pub fn draw(frame: &mut Frame, app: &App) {
                 ctx.draw(&slot);
             }
         })
-        .x_bounds([0.0, SLOT * 7.0 + BOARD_PADDING])
-        .y_bounds([0.0, SLOT * 6.0 + BOARD_PADDING]);
+        .x_bounds([0.0, SLOT * size.width() as f64 + BOARD_PADDING])
+        .y_bounds([0.0, SLOT * size.height() as f64 + BOARD_PADDING]);
     frame.render_widget(canvas, board_area);
 
-    let menu = Text::raw("(1-7) Drop Piece     (q) Exit");
+    let mut menu = if let Some(spectate) = app.spectate()
+        && app.current_page() == Page::Spectate
+    {
+        let status = if spectate.is_paused() {
+            "Paused     (space) Resume     (s) Step"
+        } else if app.is_ai_thinking() {
+            "Thinking...     (space) Pause"
+        } else {
+            "(space) Pause"
+        };
+        Text::raw(format!(
+            "{}     (+/-) Delay: {:.2}s     (a/b) Levels     (n) New Game     (q) Exit",
+            status,
+            spectate.delay().as_secs_f64()
+        ))
+    } else if app.is_ai_thinking() {
+        Text::raw("Thinking...     (c) Cancel Move     (q) Exit")
+    } else if app.current_page() == Page::Network {
+        let status = match app.network_game() {
+            Some(game) if game.is_local_turn() => "Your turn",
+            Some(game) if game.board().status() == BoardStatus::OnGoing => "Opponent's turn",
+            Some(_) => "Game over",
+            None => "Disconnected",
+        };
+        Text::raw(format!(
+            "{}     (1-{}) Drop Piece     (m) Rematch     (q) Leave",
+            status,
+            size.width()
+        ))
+    } else {
+        let pop = match app.board().variant() {
+            Variant::Standard => "",
+            Variant::PopOut => "     (p) Pop Piece",
+        };
+        Text::raw(format!(
+            "(1-{}) Drop Piece{}     (u) Undo     (r) Redo     (Ctrl-s) Save     (q) Exit",
+            size.width(),
+            pop
+        ))
+    };
+    if let Some(notice) = app.notice() {
+        menu.push_line(Line::from(notice).italic());
+    }
+    let menu = menu.centered();
     let menu_area = util::center(
         chunks[2],
         Constraint::Length(menu.width() as u16),
@@ -78,12 +126,13 @@ fn game_page_chunks(frame: &mut Frame) -> Rc<[Rect]> {
         .split(frame.area())
 }
 
-fn board_chunk(area: Rect) -> Rect {
+/// Middle of `area`, taking up half its width for a standard board of seven columns.
+fn board_chunk(area: Rect, size: BoardSize) -> Rect {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
             Constraint::Fill(1),
-            Constraint::Ratio(1, 2),
+            Constraint::Ratio(size.width() as u32, 14),
             Constraint::Fill(1),
         ])
         .split(area);
@@ -91,11 +140,17 @@ fn board_chunk(area: Rect) -> Rect {
 }
 
 fn build_board_slots(board: &ConnectFourBoard, cursor: Option<Column>) -> Vec<Circle> {
-    let mut slots = Vec::with_capacity(42);
+    let size = board.size();
+    let height = size.height() as usize;
+    let mut slots = Vec::with_capacity(size.slots() as usize);
+    let winning_line = board.winning_line();
     for (idx, slot) in board.slots().enumerate() {
-        let (row, col) = (idx % 6, idx / 6);
+        let (row, col) = (idx % height, idx / height);
 
         let color = match slot {
+            Slot::Occupied(_) if winning_line & (1 << (col * (height + 1) + row)) != 0 => {
+                WINNING_LINE_COLOR
+            }
             Slot::Occupied(player) => get_player_color(player),
             Slot::Vacant => match cursor {
                 Some(column)
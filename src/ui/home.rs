@@ -3,9 +3,12 @@ use std::rc::Rc;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    text::Text,
+    style::Stylize,
+    text::{Line, Text},
 };
 
+use crate::app::App;
+
 use super::util;
 
 const TITLE: &str = include_str!("../../assets/title.txt");
@@ -13,6 +16,14 @@ const TITLE: &str = include_str!("../../assets/title.txt");
 const MENU: &str = "
 (1) Single Player
 (2) Multiplayer
+(3) Spectate AI vs AI
+(h) Host Game
+(j) Join Game
+(l) Load Game
+(d) Difficulty: {level}
+(s) Board Size: {size}
+(v) Variant: {variant}
+(c) Connect: {connect}
 (q) Exit
 ";
 
@@ -22,28 +33,36 @@ fn prepare_chunks(frame: &Frame) -> Rc<[Rect]> {
         .constraints([
             Constraint::Fill(1),
             Constraint::Length(7),
-            Constraint::Length(4),
+            Constraint::Length(13),
             Constraint::Fill(1),
         ])
         .split(frame.area())
 }
 
-pub fn draw(frame: &mut Frame) {
+pub fn draw(frame: &mut Frame, app: &App) {
     let chunks = prepare_chunks(frame);
 
     let title = Text::raw(TITLE);
     let title_chunk = util::center(
         chunks[1],
         Constraint::Length(title.width() as u16),
-        Constraint::Length(10),
+        Constraint::Length(11),
     );
     frame.render_widget(title, title_chunk);
 
-    let menu = Text::raw(MENU);
+    let mut menu = Text::raw(
+        MENU.replace("{level}", &app.ai_level().to_string())
+            .replace("{size}", &app.board_size().to_string())
+            .replace("{variant}", &app.rules().variant().to_string())
+            .replace("{connect}", &app.rules().connect().to_string()),
+    );
+    if let Some(notice) = app.notice() {
+        menu.push_line(Line::from(notice).italic());
+    }
     let menu_chunk = util::center(
         chunks[2],
         Constraint::Length(menu.width() as u16),
-        Constraint::Length(4),
+        Constraint::Length(menu.height() as u16),
     );
     frame.render_widget(menu, menu_chunk);
 }
@@ -0,0 +1,41 @@
+use ratatui::{
+    Frame,
+    layout::Constraint,
+    style::Stylize,
+    text::{Line, Text},
+};
+
+use connect_fors::net::DEFAULT_PORT;
+
+use crate::app::{App, Page};
+
+use super::util;
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let mut text = match app.current_page() {
+        Page::Joining => Text::from(vec![
+            Line::from(format!("Join address: {}_", app.join_address())),
+            Line::from(""),
+            Line::from("(Enter) Connect     (Esc) Back"),
+        ]),
+        _ => Text::from(vec![
+            Line::from(format!(
+                "Waiting for an opponent to join on port {}...",
+                DEFAULT_PORT
+            )),
+            Line::from(""),
+            Line::from("(q) Cancel"),
+        ]),
+    };
+    if let Some(notice) = app.notice() {
+        text.push_line(Line::from(notice).italic());
+    }
+
+    let text = text.centered();
+    let area = util::center(
+        frame.area(),
+        Constraint::Length(text.width() as u16),
+        Constraint::Length(text.height() as u16),
+    );
+    frame.render_widget(text, area);
+}
@@ -4,12 +4,17 @@ use crate::app::App;
 
 mod game;
 mod home;
+mod lobby;
 mod util;
 
-pub fn draw_home(frame: &mut Frame) {
-    home::draw(frame);
+pub fn draw_home(frame: &mut Frame, app: &App) {
+    home::draw(frame, app);
 }
 
 pub fn draw_game(frame: &mut Frame, app: &App) {
     game::draw(frame, app);
 }
+
+pub fn draw_lobby(frame: &mut Frame, app: &App) {
+    lobby::draw(frame, app);
+}